@@ -0,0 +1,38 @@
+extern crate core;
+use super::stack_allocator::Allocator;
+
+/// A bundle of allocators, one per element type, handed around as a single value.
+pub trait AllocatorSet<T> {
+    type Alloc : Allocator<T>;
+    fn get_allocator(&mut self) -> &mut Self::Alloc;
+}
+
+/// Carves an aligned run of `count` default-initialized `T` off the front of a byte pool.
+///
+/// # Safety
+///
+/// Padding bytes of `T` leave the bytes under the run uninitialized, so unless `T` is `Pod` they
+/// must not be read as `u8` again, not even after the returned slice is dropped.
+pub unsafe fn split_typed_pool<'a, T : Default>(pool : &mut &'a mut [u8], count : usize) -> &'a mut [T] {
+    let size = core::mem::size_of::<T>();
+    let bytes = match size.checked_mul(count) {
+        Some(bytes) => bytes,
+        None => panic!("OOM"),
+    };
+    let pad = pool.as_ptr().align_offset(core::mem::align_of::<T>());
+    if pad > pool.len() || bytes > pool.len() - pad {
+        panic!("OOM");
+    }
+    let available = core::mem::replace(pool, &mut []);
+    let (typed_bytes, remainder) = available.split_at_mut(pad + bytes);
+    *pool = remainder;
+    let typed_ptr : *mut T = if size == 0 {
+        core::ptr::NonNull::<T>::dangling().as_ptr()
+    } else {
+        typed_bytes[pad..].as_mut_ptr() as *mut T
+    };
+    for index in 0..count {
+        core::ptr::write(typed_ptr.add(index), T::default());
+    }
+    return core::slice::from_raw_parts_mut(typed_ptr, count);
+}
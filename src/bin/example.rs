@@ -2,7 +2,6 @@
 #[macro_use]
 extern crate alloc_no_stdlib;
 extern crate core;
-use core::ops;
 mod heap_alloc;
 
 pub use heap_alloc::HeapAllocator;
@@ -20,9 +19,7 @@ extern {
 //use alloc::AllocatedSlice;
 use alloc_no_stdlib::SliceWrapper;
 use alloc_no_stdlib::SliceWrapperMut;
-use alloc_no_stdlib::AllocatedStackMemory;
 use alloc_no_stdlib::Allocator;
-use alloc_no_stdlib::StackAllocator;
 
 use alloc_no_stdlib::bzero;
 declare_stack_allocator_struct!(CallocAllocatedFreelist4, 4, calloc);
@@ -0,0 +1,158 @@
+extern crate core;
+use core::marker::PhantomData;
+use super::allocated_stack_memory::AllocatedStackMemory;
+use super::stack_allocator::Allocator;
+
+/// Power-of-two buddy allocator over a borrowed pool. Blocks are min_block << order items long;
+/// freed blocks always merge with their free buddy, so fragmentation stays bounded and both
+/// alloc_cell and free_cell walk one root-to-leaf path: O(log(pool / min_block)).
+/// Cells come back with the full length of their block, which may exceed the request.
+///
+/// The free-block tree lives in a caller-provided byte buffer of `tree_len(pool.len(), min_block)`
+/// entries, each holding one plus the largest free order below that node (zero when nothing is free).
+pub struct BuddyAllocator<'a, T : 'a> {
+    base : *mut T,
+    min_block : usize,
+    top_order : usize,
+    usable_leaves : usize,
+    tree : &'a mut [u8],
+    pub initialize : fn(&mut [T]),
+    _pool : PhantomData<&'a mut [T]>,
+}
+
+unsafe impl<'a, T : Send + 'a> Send for BuddyAllocator<'a, T> {}
+
+fn ceil_log2(val : usize) -> usize {
+    let mut order = 0;
+    while (1usize << order) < val {
+        order += 1;
+    }
+    return order;
+}
+
+impl<'a, T : 'a> BuddyAllocator<'a, T> {
+    // the number of tree bytes needed to manage pool_len items in blocks of at least min_block
+    pub fn tree_len(pool_len : usize, min_block : usize) -> usize {
+        let leaves = pool_len / min_block;
+        if leaves == 0 {
+            return 1;
+        }
+        return (2 << ceil_log2(leaves)) - 1;
+    }
+    // items past the last whole min_block of the pool are never handed out
+    pub fn new(pool : &'a mut [T],
+               min_block : usize,
+               tree : &'a mut [u8],
+               initializer : fn(&mut[T])) -> Self {
+        assert!(core::mem::size_of::<T>() != 0);
+        assert!(min_block != 0 && min_block.is_power_of_two());
+        let usable_leaves = pool.len() / min_block;
+        let top_order = ceil_log2(usable_leaves);
+        let leaves = 1usize << top_order;
+        assert!(tree.len() >= BuddyAllocator::<T>::tree_len(pool.len(), min_block));
+        for leaf in 0..leaves {
+            tree[leaves - 1 + leaf] = if leaf < usable_leaves { 1 } else { 0 };
+        }
+        let mut ret = BuddyAllocator::<'a, T>{
+            base : pool.as_mut_ptr(),
+            min_block : min_block,
+            top_order : top_order,
+            usable_leaves : usable_leaves,
+            tree : tree,
+            initialize : initializer,
+            _pool : PhantomData,
+        };
+        for node in (0..leaves - 1).rev() {
+            let order = top_order - ceil_log2(node + 2) + 1;
+            ret.merge(node, order);
+        }
+        return ret;
+    }
+    // the length of the largest cell that alloc_cell can currently satisfy
+    pub fn largest_free_block(self : &Self) -> usize {
+        if self.tree[0] == 0 {
+            return 0;
+        }
+        return self.min_block << (self.tree[0] - 1);
+    }
+    fn merge(self : &mut Self, node : usize, order : usize) {
+        let left = self.tree[2 * node + 1];
+        let right = self.tree[2 * node + 2];
+        if left as usize == order && right as usize == order {
+            self.tree[node] = order as u8 + 1;
+        } else {
+            self.tree[node] = core::cmp::max(left, right);
+        }
+    }
+    fn update_parents(self : &mut Self, mut node : usize, mut order : usize) {
+        while node != 0 {
+            node = (node - 1) / 2;
+            order += 1;
+            self.merge(node, order);
+        }
+    }
+    fn order_for(self : &Self, len : usize) -> usize {
+        return ceil_log2((len + self.min_block - 1) / self.min_block);
+    }
+}
+
+impl<'a, T : 'a> Allocator<T> for BuddyAllocator<'a, T> {
+    type AllocatedMemory = AllocatedStackMemory<'a, T>;
+    fn alloc_cell(self : &mut Self, len : usize) -> AllocatedStackMemory<'a, T> {
+        match self.try_alloc_cell(len) {
+            Some(cell) => return cell,
+            None => panic!("OOM"),
+        }
+    }
+    fn try_alloc_cell(self : &mut Self, len : usize) -> Option<AllocatedStackMemory<'a, T>> {
+        if len == 0 {
+            return Some(AllocatedStackMemory::<'a, T>::default());
+        }
+        let order = self.order_for(len);
+        if (self.tree[0] as usize) < order + 1 {
+            return None;
+        }
+        let mut node = 0;
+        let mut node_order = self.top_order;
+        while node_order != order {
+            node = 2 * node + 1;
+            if (self.tree[node] as usize) < order + 1 {
+                node += 1;
+            }
+            node_order -= 1;
+        }
+        self.tree[node] = 0;
+        self.update_parents(node, order);
+        let first_at_depth = (1usize << (self.top_order - order)) - 1;
+        let offset = (node - first_at_depth) * (self.min_block << order);
+        let cell = unsafe {
+            core::slice::from_raw_parts_mut(self.base.add(offset), self.min_block << order)
+        };
+        let fnp = self.initialize;
+        fnp(cell);
+        return Some(AllocatedStackMemory::<'a, T>{mem : cell});
+    }
+    fn free_cell(self : &mut Self, val : AllocatedStackMemory<'a, T>) {
+        let len = val.mem.len();
+        if len == 0 {
+            return;
+        }
+        let offset = (val.mem.as_ptr() as usize).wrapping_sub(self.base as usize) / core::mem::size_of::<T>();
+        let leaf = offset / self.min_block;
+        assert!(leaf < self.usable_leaves, "cell was not allocated from this BuddyAllocator");
+        // below an allocated block the tree still holds its free values, so the block is the
+        // first node without free space on the way up from the leaf
+        let mut node = (1usize << self.top_order) - 1 + leaf;
+        let mut order = 0;
+        while self.tree[node] != 0 && node != 0 {
+            node = (node - 1) / 2;
+            order += 1;
+        }
+        let first_at_depth = (1usize << (self.top_order - order)) - 1;
+        assert!(self.tree[node] == 0 && val.mem.len() == self.min_block << order
+                && offset == (node - first_at_depth) * (self.min_block << order),
+                "cell was not allocated from this BuddyAllocator");
+        self.tree[node] = order as u8 + 1;
+        self.update_parents(node, order);
+    }
+}
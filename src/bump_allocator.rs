@@ -0,0 +1,116 @@
+extern crate core;
+use core::marker::PhantomData;
+use super::allocated_stack_memory::AllocatedStackMemory;
+use super::stack_allocator::Allocator;
+use super::{bzero, CallocBackingStore};
+#[cfg(not(feature="no-stdlib"))]
+use std::boxed::Box;
+
+/// Linear allocator: alloc_cell advances an offset into a borrowed slice and free_cell only
+/// reclaims space when handed the most recent cell. Everything else comes back with reset or
+/// when a scope ends.
+pub struct BumpAllocator<'a, T : 'a> {
+    base : *mut T,
+    len : usize,
+    offset : usize,
+    pub initialize : fn(&mut [T]),
+    _pool : PhantomData<&'a mut [T]>,
+}
+
+unsafe impl<'a, T : Send + 'a> Send for BumpAllocator<'a, T> {}
+
+impl<'a, T : 'a> BumpAllocator<'a, T> {
+    pub fn new(pool : &'a mut [T], initializer : fn(&mut[T])) -> Self {
+        return BumpAllocator::<'a, T>{
+            base : pool.as_mut_ptr(),
+            len : pool.len(),
+            offset : 0,
+            initialize : initializer,
+            _pool : PhantomData,
+        };
+    }
+    pub fn capacity(self : &Self) -> usize {
+        return self.len;
+    }
+    pub fn used(self : &Self) -> usize {
+        return self.offset;
+    }
+    pub fn remaining(self : &Self) -> usize {
+        return self.len - self.offset;
+    }
+    /// Reclaims every cell handed out so far.
+    ///
+    /// # Safety
+    ///
+    /// No cell returned by this allocator may still be alive: later allocations reuse their memory.
+    pub unsafe fn reset(self : &mut Self) {
+        self.offset = 0;
+    }
+    // runs f with an allocator over the unused tail of this one; cells cannot outlive the call,
+    // so all of its space is reclaimed when f returns
+    pub fn scope<R, F>(self : &mut Self, f : F) -> R
+        where F : for<'b> FnOnce(&mut BumpAllocator<'b, T>) -> R {
+        let tail = unsafe {
+            core::slice::from_raw_parts_mut(self.base.add(self.offset), self.len - self.offset)
+        };
+        let mut child = BumpAllocator::new(tail, self.initialize);
+        return f(&mut child);
+    }
+}
+
+impl<'a, T : 'a> Allocator<T> for BumpAllocator<'a, T> {
+    type AllocatedMemory = AllocatedStackMemory<'a, T>;
+    fn alloc_cell(self : &mut Self, len : usize) -> AllocatedStackMemory<'a, T> {
+        match self.try_alloc_cell(len) {
+            Some(cell) => return cell,
+            None => panic!("OOM"),
+        }
+    }
+    fn try_alloc_cell(self : &mut Self, len : usize) -> Option<AllocatedStackMemory<'a, T>> {
+        if len == 0 {
+            return Some(AllocatedStackMemory::<'a, T>::default());
+        }
+        if len > self.len - self.offset {
+            return None;
+        }
+        let cell = unsafe {
+            core::slice::from_raw_parts_mut(self.base.add(self.offset), len)
+        };
+        self.offset += len;
+        let fnp = self.initialize;
+        fnp(cell);
+        return Some(AllocatedStackMemory::<'a, T>{mem : cell});
+    }
+    fn free_cell(self : &mut Self, val : AllocatedStackMemory<'a, T>) {
+        let len = val.mem.len();
+        if len != 0 && len <= self.offset
+           && val.mem.as_ptr() == unsafe { self.base.add(self.offset - len) } as *const T {
+            self.offset -= len;
+        }
+    }
+}
+
+impl<'a, T : Default + 'a> From<&'a mut [T]> for BumpAllocator<'a, T> {
+    fn from(pool : &'a mut [T]) -> Self {
+        return BumpAllocator::new(pool, bzero);
+    }
+}
+
+impl<'a, T : Default + 'a, const N : usize> From<&'a mut [T; N]> for BumpAllocator<'a, T> {
+    fn from(pool : &'a mut [T; N]) -> Self {
+        return BumpAllocator::new(&mut pool[..], bzero);
+    }
+}
+
+#[cfg(not(feature="no-stdlib"))]
+impl<'a, T : Default + 'a> From<&'a mut Box<[T]>> for BumpAllocator<'a, T> {
+    fn from(pool : &'a mut Box<[T]>) -> Self {
+        return BumpAllocator::new(&mut pool[..], bzero);
+    }
+}
+
+impl<'a, 'b : 'a, T : Default + 'b> From<&'a mut CallocBackingStore<'b, T>> for BumpAllocator<'a, T> {
+    fn from(pool : &'a mut CallocBackingStore<'b, T>) -> Self {
+        return BumpAllocator::new(&mut pool.data[..], bzero);
+    }
+}
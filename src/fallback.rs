@@ -0,0 +1,96 @@
+extern crate core;
+use core::ops;
+use super::allocated_memory::{SliceWrapper, SliceWrapperMut};
+use super::stack_allocator::Allocator;
+
+/// A cell from a `Fallback` allocator, tagged with the allocator that produced it.
+pub enum FallbackCell<P, S> {
+    Primary(P),
+    Secondary(S),
+}
+
+impl<P : Default, S> Default for FallbackCell<P, S> {
+    fn default() -> Self {
+        return FallbackCell::Primary(P::default());
+    }
+}
+
+impl<T, P : SliceWrapper<T>, S : SliceWrapper<T>> SliceWrapper<T> for FallbackCell<P, S> {
+    fn slice(& self) -> & [T] {
+        match *self {
+            FallbackCell::Primary(ref cell) => return cell.slice(),
+            FallbackCell::Secondary(ref cell) => return cell.slice(),
+        }
+    }
+}
+
+impl<T, P : SliceWrapperMut<T>, S : SliceWrapperMut<T>> SliceWrapperMut<T> for FallbackCell<P, S> {
+    fn slice_mut(& mut self) -> & mut [T] {
+        match *self {
+            FallbackCell::Primary(ref mut cell) => return cell.slice_mut(),
+            FallbackCell::Secondary(ref mut cell) => return cell.slice_mut(),
+        }
+    }
+}
+
+impl<P : ops::Index<usize>, S : ops::Index<usize, Output = P::Output>> ops::Index<usize> for FallbackCell<P, S> {
+    type Output = P::Output;
+    fn index(&self, index : usize) -> &P::Output {
+        match *self {
+            FallbackCell::Primary(ref cell) => return &cell[index],
+            FallbackCell::Secondary(ref cell) => return &cell[index],
+        }
+    }
+}
+
+impl<P : ops::IndexMut<usize>, S : ops::IndexMut<usize, Output = P::Output>> ops::IndexMut<usize> for FallbackCell<P, S> {
+    fn index_mut(&mut self, index : usize) -> &mut P::Output {
+        match *self {
+            FallbackCell::Primary(ref mut cell) => return &mut cell[index],
+            FallbackCell::Secondary(ref mut cell) => return &mut cell[index],
+        }
+    }
+}
+
+/// Serves each request from primary while it can, e.g. a StackAllocator over a small fixed
+/// pool, and from secondary (HeapAlloc or a second pool) once primary's try_alloc_cell fails.
+/// free_cell hands every cell back to the allocator that produced it.
+pub struct Fallback<P, S> {
+    pub primary : P,
+    pub secondary : S,
+}
+
+impl<P, S> Fallback<P, S> {
+    pub fn new(primary : P, secondary : S) -> Self {
+        return Fallback::<P, S>{
+            primary : primary,
+            secondary : secondary,
+        };
+    }
+    pub fn into_inner(self) -> (P, S) {
+        return (self.primary, self.secondary);
+    }
+}
+
+impl<T, P : Allocator<T>, S : Allocator<T>> Allocator<T> for Fallback<P, S>
+    where S::AllocatedMemory : ops::IndexMut<usize, Output = <P::AllocatedMemory as ops::Index<usize>>::Output> {
+    type AllocatedMemory = FallbackCell<P::AllocatedMemory, S::AllocatedMemory>;
+    fn alloc_cell(self : &mut Self, len : usize) -> Self::AllocatedMemory {
+        if let Some(cell) = self.primary.try_alloc_cell(len) {
+            return FallbackCell::Primary(cell);
+        }
+        return FallbackCell::Secondary(self.secondary.alloc_cell(len));
+    }
+    fn try_alloc_cell(self : &mut Self, len : usize) -> Option<Self::AllocatedMemory> {
+        if let Some(cell) = self.primary.try_alloc_cell(len) {
+            return Some(FallbackCell::Primary(cell));
+        }
+        return self.secondary.try_alloc_cell(len).map(FallbackCell::Secondary);
+    }
+    fn free_cell(self : &mut Self, data : Self::AllocatedMemory) {
+        match data {
+            FallbackCell::Primary(cell) => self.primary.free_cell(cell),
+            FallbackCell::Secondary(cell) => self.secondary.free_cell(cell),
+        }
+    }
+}
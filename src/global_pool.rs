@@ -0,0 +1,66 @@
+extern crate core;
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// Storage for a global free list or heap. It is only reachable through the GlobalPool that owns it.
+pub struct GlobalCell<A : ?Sized> {
+    data : UnsafeCell<A>,
+}
+
+unsafe impl<A : ?Sized + Send> Sync for GlobalCell<A> {}
+
+impl<A> GlobalCell<A> {
+    pub const fn new(data : A) -> Self {
+        return GlobalCell::<A>{data : UnsafeCell::new(data)};
+    }
+}
+
+/// Storage for a global free list of N slots, left uninitialized until the pool is claimed.
+pub type GlobalFreelist<T, const N : usize> = GlobalCell<[MaybeUninit<&'static mut [T]>; N]>;
+
+impl<T : 'static, const N : usize> GlobalCell<[MaybeUninit<&'static mut [T]>; N]> {
+    pub const fn uninit_freelist() -> Self {
+        // an array of MaybeUninit needs no initialization
+        return GlobalCell::new(unsafe { MaybeUninit::<[MaybeUninit<&'static mut [T]>; N]>::uninit().assume_init() });
+    }
+}
+
+/// A free list and heap in static memory that may be bound to exactly one allocator.
+pub struct GlobalPool<T : 'static> {
+    claimed : AtomicBool,
+    // filled with empty slots by claim, so a static of any length can start out uninitialized
+    freelist : &'static GlobalCell<[MaybeUninit<&'static mut [T]>]>,
+    heap : &'static GlobalCell<[T]>,
+}
+
+impl<T : 'static> GlobalPool<T> {
+    /// # Safety
+    /// freelist and heap must not be handed to any other GlobalPool; define_allocator_memory_pool!
+    /// guarantees this by giving each pool its own hidden statics.
+    pub const unsafe fn new(freelist : &'static GlobalCell<[MaybeUninit<&'static mut [T]>]>,
+                            heap : &'static GlobalCell<[T]>) -> Self {
+        return GlobalPool::<T>{
+            claimed : AtomicBool::new(false),
+            freelist : freelist,
+            heap : heap,
+        };
+    }
+    // hands out the free list and heap the first time it is called and None on every later call
+    pub fn claim(self : &'static Self) -> Option<(&'static mut [&'static mut [T]], &'static mut [T])> {
+        if self.claimed.swap(true, Ordering::AcqRel) {
+            return None;
+        }
+        unsafe {
+            let slots = &mut *self.freelist.data.get();
+            for slot in slots.iter_mut() {
+                *slot = MaybeUninit::new(&mut []);
+            }
+            let freelist = core::slice::from_raw_parts_mut(slots.as_mut_ptr() as *mut &'static mut [T], slots.len());
+            return Some((freelist, &mut *self.heap.data.get()));
+        }
+    }
+    pub fn is_claimed(self : &Self) -> bool {
+        return self.claimed.load(Ordering::Acquire);
+    }
+}
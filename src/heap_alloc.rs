@@ -2,7 +2,7 @@
 use std;
 
 
-use super::{SliceWrapper, SliceWrapperMut, Allocator};
+use super::Allocator;
 
 use core;
 use core::ops;
@@ -62,11 +62,78 @@ impl<T : core::clone::Clone> super::Allocator<T> for HeapAlloc<T> {
        let b = v.into_boxed_slice();
        return WrapBox::<T>{b : b};
    }
+   fn try_alloc_cell(self : &mut HeapAlloc<T>, len : usize) -> Option<WrapBox<T>> {
+       let mut v : std::vec::Vec<T> = std::vec::Vec::new();
+       if v.try_reserve_exact(len).is_err() {
+           return None;
+       }
+       v.resize(len, self.default_value.clone());
+       return Some(WrapBox::<T>{b : v.into_boxed_slice()});
+   }
    fn free_cell(self : &mut HeapAlloc<T>, _data : WrapBox<T>) {
 
    }
 }
 
+// a boxed slice of len all-zero items straight from alloc_zeroed, which large requests get as
+// untouched pages from the OS instead of writing every item
+fn try_zeroed_boxed_slice<T : super::Zeroable>(len : usize) -> Option<Box<[T]>> {
+    let layout = match std::alloc::Layout::array::<T>(len) {
+        Ok(layout) => layout,
+        Err(_) => return None,
+    };
+    unsafe {
+        if layout.size() == 0 {
+            return Some((0..len).map(|_| core::mem::zeroed::<T>()).collect());
+        }
+        let ptr = std::alloc::alloc_zeroed(layout) as *mut T;
+        if ptr.is_null() {
+            return None;
+        }
+        return Some(Box::from_raw(core::ptr::slice_from_raw_parts_mut(ptr, len)));
+    }
+}
+
+fn zeroed_boxed_slice<T : super::Zeroable>(len : usize) -> Box<[T]> {
+    match try_zeroed_boxed_slice(len) {
+        Some(b) => return b,
+        None => panic!("OOM"),
+    }
+}
+
+/// Like HeapAlloc with a zero default value, but cells come from alloc_zeroed rather than
+/// cloning the default into every item.
+pub struct HeapAllocZeroed<T : super::Zeroable> {
+   _element : core::marker::PhantomData<T>,
+}
+
+impl<T : super::Zeroable> HeapAllocZeroed<T> {
+   pub fn new() -> HeapAllocZeroed<T> {
+      return HeapAllocZeroed::<T>{_element : core::marker::PhantomData};
+   }
+}
+
+impl<T : super::Zeroable> Default for HeapAllocZeroed<T> {
+   fn default() -> Self {
+      return HeapAllocZeroed::<T>::new();
+   }
+}
+
+impl<T : super::Zeroable> super::Allocator<T> for HeapAllocZeroed<T> {
+   type AllocatedMemory = WrapBox<T>;
+   fn alloc_cell(self : &mut Self, len : usize) -> WrapBox<T> {
+       return WrapBox::<T>{b : zeroed_boxed_slice(len)};
+   }
+   fn try_alloc_cell(self : &mut Self, len : usize) -> Option<WrapBox<T>> {
+       return try_zeroed_boxed_slice(len).map(|b| WrapBox::<T>{b : b});
+   }
+   fn free_cell(self : &mut Self, _data : WrapBox<T>) {
+
+   }
+}
+
+/// Skips HeapAlloc's per-item clone of the default value. Restricted to Pod types, for which
+/// the all-zero memory it hands out is a valid value.
 #[cfg(feature="unsafe")]
 pub struct HeapAllocUninitialized<T>{
    #[allow(dead_code)]
@@ -81,14 +148,13 @@ impl<T> HeapAllocUninitialized<T>{
 }
 
 #[cfg(feature="unsafe")]
-impl<T> super::Allocator<T> for HeapAllocUninitialized<T> {
+impl<T : super::Pod> super::Allocator<T> for HeapAllocUninitialized<T> {
    type AllocatedMemory = WrapBox<T>;
    fn alloc_cell(self : &mut Self, len : usize) -> WrapBox<T> {
-
-       let mut v : std::vec::Vec<T> = std::vec::Vec::with_capacity(len);
-       unsafe {v.set_len(len)};
-       let b = v.into_boxed_slice();
-       return WrapBox::<T>{b : b};
+       return WrapBox::<T>{b : zeroed_boxed_slice(len)};
+   }
+   fn try_alloc_cell(self : &mut Self, len : usize) -> Option<WrapBox<T>> {
+       return try_zeroed_boxed_slice(len).map(|b| WrapBox::<T>{b : b});
    }
    fn free_cell(self : &mut Self, _data : WrapBox<T>) {
 
@@ -120,15 +186,18 @@ impl<'a, T : core::clone::Clone+'a> HeapPrealloc<'a, T> {
             free_list_start : freelist_size,
             free_list_overflow_count : 0,
             initialize : initializer,
+            zero_policy : super::ZeroPolicy::DirtyWatermark,
+            dirty_watermark : 0,
+            pool_start : memory_pool.as_ptr() as usize,
+            pool_len : memory_pool.len(),
         };
         retval.free_cell(super::AllocatedStackMemory::<T>{mem:&mut*memory_pool});
         return retval;
     }
+    // the pool comes back zeroed, which is only a valid value for Pod types
     #[cfg(feature="unsafe")]
-    pub unsafe fn new_uninitialized_memory_pool(len : usize) -> Box<[T]> {
-        let mut v : std::vec::Vec<T> = std::vec::Vec::with_capacity(len);
-        v.set_len(len);
-        return v.into_boxed_slice();
+    pub unsafe fn new_uninitialized_memory_pool(len : usize) -> Box<[T]> where T : super::Pod {
+        return zeroed_boxed_slice(len);
     }
 }
 
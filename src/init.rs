@@ -1,39 +1,8 @@
+// [$expr; $n] for element types that are not Copy, e.g. the empty slices of a free list;
+// $expr is evaluated once per element
 #[macro_export]
 macro_rules! static_array {
-    (@accum (0, $($_ignored:expr),*) -> ($($body:tt)*))
-        => {static_array!(@as_expr [$($body)*])};
-    (@accum (1, $($expr:expr),*) -> ($($body:tt)*))
-        => {static_array!(@accum (0, $($expr),*) -> ($($body)* $($expr,)*))};
-    (@accum (2, $($expr:expr),*) -> ($($body:tt)*))
-        => {static_array!(@accum (0, $($expr),*) -> ($($body)* $($expr,)* $($expr,)*))};
-    (@accum (4, $($expr:expr),*) -> ($($body:tt)*))
-        => {static_array!(@accum (2, $($expr,)* $($expr),*) -> ($($body)*))};
-    (@accum (8, $($expr:expr),*) -> ($($body:tt)*))
-        => {static_array!(@accum (4, $($expr,)* $($expr),*) -> ($($body)*))};
-    (@accum (16, $($expr:expr),*) -> ($($body:tt)*))
-        => {static_array!(@accum (8, $($expr,)* $($expr),*) -> ($($body)*))};
-    (@accum (32, $($expr:expr),*) -> ($($body:tt)*))
-        => {static_array!(@accum (16, $($expr,)* $($expr),*) -> ($($body)*))};
-    (@accum (64, $($expr:expr),*) -> ($($body:tt)*))
-        => {static_array!(@accum (32, $($expr,)* $($expr),*) -> ($($body)*))};
-    (@accum (128, $($expr:expr),*) -> ($($body:tt)*))
-        => {static_array!(@accum (64, $($expr,)* $($expr),*) -> ($($body)*))};
-    (@accum (256, $($expr:expr),*) -> ($($body:tt)*))
-        => {static_array!(@accum (128, $($expr,)* $($expr),*) -> ($($body)*))};
-    (@accum (512, $($expr:expr),*) -> ($($body:tt)*))
-        => {static_array!(@accum (256, $($expr,)* $($expr),*) -> ($($body)*))};
-    (@accum (1024, $($expr:expr),*) -> ($($body:tt)*))
-        => {static_array!(@accum (512, $($expr,)* $($expr),*) -> ($($body)*))};
-    (@accum (2048, $($expr:expr),*) -> ($($body:tt)*))
-        => {static_array!(@accum (1024, $($expr,)* $($expr),*) -> ($($body)*))};
-    (@accum (4096, $($expr:expr),*) -> ($($body:tt)*))
-        => {static_array!(@accum (2048, $($expr,)* $($expr),*) -> ($($body)*))};
-    (@accum (8192, $($expr:expr),*) -> ($($body:tt)*))
-        => {static_array!(@accum (4096, $($expr,)* $($expr),*) -> ($($body)*))};
-
-    (@as_expr $expr:expr) => {$expr};
-
-    [$expr:expr; $n:tt] => { static_array!(@accum ($n, $expr) -> ()) };
+    [$expr:expr; $n:expr] => { $crate::core_array::from_fn::<_, {$n}, _>(|_| $expr) };
 }
 
 
@@ -50,7 +19,7 @@ macro_rules! define_stack_allocator_traits(
     ($name : ident, $freelist_size : tt, stack) => {
         impl<'a, T: 'a> Default for $name<'a, T> {
             fn default() -> Self {
-                return $name::<'a, T>{freelist : static_array!(&mut[]; $freelist_size)};
+                return $name::<'a, T>{freelist : static_array!(&mut [][..]; $freelist_size)};
             }
         }
         define_stack_allocator_traits!($name, generic);
@@ -72,30 +41,30 @@ macro_rules! define_stack_allocator_traits(
     
         impl<'a, T: 'a> Default for $name<'a, T> {
             fn default() -> Self {
-                return $name::<'a, T>{freelist : static_array!(&mut[]; $freelist_size)};
+                return $name::<'a, T>{freelist : static_array!(&mut [][..]; $freelist_size)};
             }
         }
         define_stack_allocator_traits!($name, generic);
     };
     ($name : ident, generic) => {
-        impl<'a, T: 'a> SliceWrapper<&'a mut[T]> for $name<'a, T> {
+        impl<'a, T: 'a> $crate::SliceWrapper<&'a mut[T]> for $name<'a, T> {
             fn slice(& self) -> & [&'a mut[T]] {
                 return & self.freelist;
             }
         }
-        impl<'a, T: 'a> SliceWrapperMut<&'a mut [T]> for $name<'a, T> {
+        impl<'a, T: 'a> $crate::SliceWrapperMut<&'a mut [T]> for $name<'a, T> {
             fn slice_mut(& mut self) ->&mut [&'a mut [T]] {
                 return &mut self.freelist;
             }
         }
-        impl<'a, T: 'a> ops::Index<usize> for $name<'a, T> {
+        impl<'a, T: 'a> $crate::core_ops::Index<usize> for $name<'a, T> {
             type Output = [T];
             fn index<'b> (&'b self, _index : usize) -> &'b [T] {
                 return &self.freelist[_index];
             }
         }
 
-        impl<'a, T: 'a> ops::IndexMut<usize> for $name<'a, T> {
+        impl<'a, T: 'a> $crate::core_ops::IndexMut<usize> for $name<'a, T> {
             fn index_mut<'b>(&'b mut self, _index : usize) -> &'b mut [T] {
                 return &mut self.freelist[_index];
             }
@@ -106,83 +75,155 @@ macro_rules! define_stack_allocator_traits(
 #[macro_export]
 macro_rules! declare_stack_allocator_struct(
     (@as_expr $expr : expr) => {$expr};
-    (@new_method $name : ident, $freelist_size : tt) => {
-        impl<'a, T: 'a> $name<'a, T> {
-          fn new_allocator(global_buffer : &'a mut [T],
-                           initializer : fn(&mut[T])) -> StackAllocator<'a, T, $name<'a, T> > {
-              let mut retval = StackAllocator::<T, $name<T> > {
+    // an alias cannot take derives: Debug is already derived by StackFreelist, anything else is
+    // rejected rather than silently dropped
+    (@alias [$($kept : tt)*] #[derive(Debug)] $($rest : tt)*) => {
+        declare_stack_allocator_struct!(@alias [$($kept)*] $($rest)*);
+    };
+    (@alias [$($kept : tt)*] #[derive $($derive : tt)*] $($rest : tt)*) => {
+        compile_error!("declare_stack_allocator_struct! declares an alias of StackFreelist, which only derives Debug");
+    };
+    (@alias [$($kept : tt)*] #[$($attr : tt)*] $($rest : tt)*) => {
+        declare_stack_allocator_struct!(@alias [$($kept)* #[$($attr)*]] $($rest)*);
+    };
+    (@alias [$($kept : tt)*] ; $vis : vis $name : ident, $freelist_size : tt) => {
+        $($kept)*
+        #[allow(dead_code)]
+        $vis type $name<'a, T> = $crate::StackFreelist<'a, T, {$freelist_size}>;
+    };
+    ($(#[$($attr : tt)*])* $vis : vis $name :ident, $freelist_size : tt, malloc) => {
+        declare_stack_allocator_struct!($(#[$($attr)*])* $vis $name, $freelist_size, calloc);
+    };
+    ($(#[$($attr : tt)*])* $vis : vis $name :ident, $freelist_size : tt, mlock) => {
+        declare_stack_allocator_struct!($(#[$($attr)*])* $vis $name, $freelist_size, calloc);
+    };
+    ($(#[$($attr : tt)*])* $vis : vis $name :ident, $freelist_size : tt, calloc) => {
+        declare_stack_allocator_struct!($(#[$($attr)*])* $vis $name, $freelist_size, stack);
+    };
+    ($(#[$($attr : tt)*])* $vis : vis $name :ident, $freelist_size : tt, stack) => {
+        declare_stack_allocator_struct!(@alias [] $(#[$($attr)*])* ; $vis $name, $freelist_size);
+    };
+    ($(#[$attr : meta])* $vis : vis $name :ident, $freelist_size : expr, global) => {
+       $(#[$attr])*
+       $vis struct $name <'a, T: 'a> {pub freelist : &'a mut [&'a mut [T]]}
+       define_stack_allocator_traits!($name, global);
+       impl<'a, T: 'a> $name<'a, T> {
+          #[allow(dead_code)]
+          $vis fn new_allocator(initializer : fn (&mut[T])) -> $crate::StackAllocator<'a, T, $name<'a, T> > {
+              return $crate::StackAllocator::<T, $name<T> > {
                   nop : &mut [],
                   system_resources : $name::<T>::default(),
-                  free_list_start : declare_stack_allocator_struct!(@as_expr $freelist_size),
+                  free_list_start : 0,
                   free_list_overflow_count : 0,
                   initialize : initializer,
+                  zero_policy : $crate::ZeroPolicy::DirtyWatermark,
+                  dirty_watermark : 0,
+                  pool_start : 0,
+                  pool_len : 0,
               };
-              retval.free_cell(AllocatedStackMemory::<T>{mem:global_buffer});
-              return retval;
           }
-        }
+       }
     };
+);
 
-    (@new_calloc_method $name : ident, $freelist_size : tt) => {
-        impl<'a, T: 'a> $name<'a, T> {
-          fn new_allocator(mut global_buffer : &'a mut [T],
-                           initializer : fn(&mut[T])) -> StackAllocator<'a, T, $name<'a, T> > {
-              let mut retval = StackAllocator::<T, $name<T> > {
-                  nop : &mut [],
-                  system_resources : $name::<T>::default(),
-                  free_list_start : declare_stack_allocator_struct!(@as_expr $freelist_size),
-                  free_list_overflow_count : 0,
-                  initialize : initializer,
+#[macro_export]
+macro_rules! declare_allocator_set(
+    ($(#[$attr : meta])* $vis : vis $name : ident, $freelist : ident, shared, {$($T : ty),* $(,)*}) => {
+        $(#[$attr])*
+        $vis struct $name<'a> {
+            bytes : $crate::StackAllocator<'a, u8, $freelist<'a, u8> >,
+        }
+        impl<'a> $name<'a> {
+          // every listed type draws its cells from the whole of pool
+          $vis fn new_allocator_set(pool : &'a mut [u8], initializer : fn(&mut[u8])) -> Self {
+              let mut pad = pool.as_ptr().align_offset($crate::TYPED_CELL_GRANULE);
+              if pad > pool.len() {
+                  pad = pool.len();
+              }
+              return $name {
+                  bytes : $freelist::<u8>::new_allocator(&mut pool[pad..], initializer),
               };
-              retval.free_cell(AllocatedStackMemory::<T>{mem:core::mem::replace(&mut global_buffer, &mut[])});
-              return retval;
           }
         }
-    };
-    ($name :ident, $freelist_size : tt, malloc) => {
-        declare_stack_allocator_struct!($name, $freelist_size, calloc);
-    };
-    ($name :ident, $freelist_size : tt, calloc) => {
-        struct $name<'a, T : 'a> {
-            freelist : [&'a mut [T]; declare_stack_allocator_struct!(@as_expr $freelist_size)],
+        $(
+        impl<'a> $crate::AllocatorSet<$T> for $name<'a> {
+            type Alloc = Self;
+            fn get_allocator(&mut self) -> &mut Self {
+                return self;
+            }
         }
-        define_stack_allocator_traits!($name,
-                                       $freelist_size,
-                                       calloc);
-        declare_stack_allocator_struct!( @new_calloc_method $name, $freelist_size);
-    };
-    ($name :ident, $freelist_size : tt, stack) => {
-        struct $name<'a, T : 'a> {
-            freelist : [&'a mut [T];declare_stack_allocator_struct!(@as_expr $freelist_size)],
-            // can't borrow here: make it on stack-- heap : core::cell::RefCell<[T; $heap_size]>
+        impl<'a> $crate::Allocator<$T> for $name<'a> {
+            type AllocatedMemory = $crate::AllocatedStackMemory<'a, $T>;
+            fn alloc_cell(&mut self, len : usize) -> $crate::AllocatedStackMemory<'a, $T> {
+                return $crate::alloc_typed_cell::<$T, _>(&mut self.bytes, len);
+            }
+            fn try_alloc_cell(&mut self, len : usize) -> Option<$crate::AllocatedStackMemory<'a, $T>> {
+                return $crate::try_alloc_typed_cell::<$T, _>(&mut self.bytes, len);
+            }
+            fn free_cell(&mut self, data : $crate::AllocatedStackMemory<'a, $T>) {
+                $crate::free_typed_cell::<$T, _>(&mut self.bytes, data);
+            }
         }
-        define_stack_allocator_traits!($name,
-                                       $freelist_size,
-                                       stack);
-        declare_stack_allocator_struct!( @new_method $name, $freelist_size);
+        )*
     };
-    ($name :ident, $freelist_size : expr, global) => {
-       struct $name <'a, T: 'a> {freelist : &'a mut [&'a mut [T]]}
-       define_stack_allocator_traits!($name, global);
-       impl<'a, T: 'a> $name<'a, T> {
-          fn new_allocator(initializer : fn (&mut[T])) -> StackAllocator<'a, T, $name<'a, T> > {
-              return StackAllocator::<T, $name<T> > {
-                  nop : &mut [],
-                  system_resources : $name::<T>::default(),
-                  free_list_start : 0,
-                  free_list_overflow_count : 0,
-                  initialize : initializer,
+    ($(#[$attr : meta])* $vis : vis $name : ident, $freelist : ident, {$($field : ident : $T : ty),* $(,)*}) => {
+        $(#[$attr])*
+        $vis struct $name<'a> {
+            $($field : $crate::StackAllocator<'a, $T, $freelist<'a, $T> >,)*
+        }
+        impl<'a> $name<'a> {
+          /// Each argument is the element count carved out of pool for that type and its initializer.
+          ///
+          /// # Safety
+          ///
+          /// Unless every listed type is `Pod`, pool must not be read as bytes again once the set
+          /// is dropped: padding in the items leaves those bytes uninitialized.
+          $vis unsafe fn new_allocator_set(mut pool : &'a mut [u8],
+                                           $($field : (usize, fn(&mut[$T]))),*) -> Self {
+              return $name {
+                  $($field : $freelist::<$T>::new_allocator(
+                      $crate::split_typed_pool::<$T>(&mut pool, $field.0), $field.1),)*
               };
           }
-       }
+        }
+        $(
+        impl<'a> $crate::AllocatorSet<$T> for $name<'a> {
+            type Alloc = $crate::StackAllocator<'a, $T, $freelist<'a, $T> >;
+            fn get_allocator(&mut self) -> &mut Self::Alloc {
+                return &mut self.$field;
+            }
+        }
+        impl<'a> $crate::Allocator<$T> for $name<'a> {
+            type AllocatedMemory = $crate::AllocatedStackMemory<'a, $T>;
+            fn alloc_cell(&mut self, len : usize) -> $crate::AllocatedStackMemory<'a, $T> {
+                return $crate::Allocator::<$T>::alloc_cell(&mut self.$field, len);
+            }
+            fn try_alloc_cell(&mut self, len : usize) -> Option<$crate::AllocatedStackMemory<'a, $T>> {
+                return $crate::Allocator::<$T>::try_alloc_cell(&mut self.$field, len);
+            }
+            fn free_cell(&mut self, data : $crate::AllocatedStackMemory<'a, $T>) {
+                $crate::Allocator::<$T>::free_cell(&mut self.$field, data);
+            }
+        }
+        )*
     };
 );
+
+// evaluates to Some(()) once the pool is bound and to None if it was already bound elsewhere
 #[macro_export]
 macro_rules! bind_global_buffers_to_allocator(
-    ($allocator : expr, $buffer : ident, $T : ty) => {
-        $allocator.free_list_start = $buffer::freelist.len();
-        $allocator.system_resources.freelist = &mut $buffer::freelist;
-        $allocator.free_cell(AllocatedStackMemory::<$T>{mem:&mut $buffer::heap});
+    ($allocator : expr, $buffer : expr, $T : ty) => {
+        match $buffer.claim() {
+            Some((freelist, heap)) => {
+                $allocator.free_list_start = freelist.len();
+                $allocator.system_resources.freelist = freelist;
+                $allocator.pool_start = heap.as_ptr() as usize;
+                $allocator.pool_len = heap.len();
+                $crate::Allocator::free_cell(&mut $allocator, $crate::AllocatedStackMemory::<$T>{mem:heap});
+                Some(())
+            },
+            None => None,
+        }
     };
 );
 
@@ -203,6 +244,9 @@ macro_rules! define_allocator_memory_pool(
     ($freelist_size : tt, $T : ty, [0; $heap_size : expr], malloc_no_free) => {
        alloc_no_stdlib::CallocBackingStore::<$T>::new($heap_size, alloc_no_stdlib::AllocatorC::Malloc(malloc), free, false);
     };
+    ($freelist_size : tt, $T : ty, [0; $heap_size : expr], mlock) => {
+       $crate::LockedBackingStore::<$T>::new($heap_size)
+    };
     ($freelist_size : tt, $T : ty, [$default_value : expr; $heap_size : expr], heap) => {
        (vec![$default_value; $heap_size]).into_boxed_slice();
     };
@@ -210,12 +254,18 @@ macro_rules! define_allocator_memory_pool(
        [$default_value; $heap_size];
     };
     ($freelist_size : tt, $T : ty, [$default_value : expr; $heap_size : expr], global, $name : ident) => {
-       pub mod $name {
-           pub static mut freelist : [&'static mut [$T];
-                                  define_allocator_memory_pool!(@as_expr $freelist_size)]
-               = static_array!(&mut[]; $freelist_size);
-           pub static mut heap : [$T; $heap_size] = [$default_value; $heap_size];
-       }
+       #[allow(non_upper_case_globals)]
+       pub static $name : $crate::GlobalPool<$T> = unsafe {
+           $crate::GlobalPool::<$T>::new({
+               static FREELIST : $crate::GlobalFreelist<$T, {$freelist_size}>
+                   = $crate::GlobalFreelist::<$T, {$freelist_size}>::uninit_freelist();
+               &FREELIST
+           }, {
+               static HEAP : $crate::GlobalCell<[$T; $heap_size]>
+                   = $crate::GlobalCell::new([$default_value; $heap_size]);
+               &HEAP
+           })
+       };
     };
 
 );
@@ -15,13 +15,65 @@ pub use allocated_memory::AllocatedSlice;
 pub use allocated_stack_memory::AllocatedStackMemory;
 pub use stack_allocator::Allocator;
 pub use stack_allocator::StackAllocator;
+pub use stack_allocator::ZeroPolicy;
+mod stack_freelist;
+pub use stack_freelist::StackFreelist;
+#[doc(hidden)]
+pub use core::ops as core_ops;
+#[doc(hidden)]
+pub use core::array as core_array;
 mod heap_alloc;
 #[cfg(not(feature="no-stdlib"))]
 pub use heap_alloc::HeapAlloc;
 #[cfg(not(feature="no-stdlib"))]
 pub use heap_alloc::HeapPrealloc;
+#[cfg(not(feature="no-stdlib"))]
+pub use heap_alloc::HeapAllocZeroed;
 #[cfg(all(not(feature="no-stdlib"), feature="unsafe"))]
 pub use heap_alloc::HeapAllocUninitialized;
+mod ring_buffer;
+pub use ring_buffer::AllocRingBuffer;
+mod spin_lock;
+mod sync_stack_allocator;
+pub use sync_stack_allocator::{SyncStackAllocator, SyncStackAllocatorHandle};
+mod slab_allocator;
+pub use slab_allocator::SlabAllocator;
+mod buddy_allocator;
+pub use buddy_allocator::BuddyAllocator;
+mod bump_allocator;
+pub use bump_allocator::BumpAllocator;
+mod tlsf_allocator;
+pub use tlsf_allocator::{TlsfAllocator, TlsfTag, TLSF_FIRST_LEVELS, TLSF_SECOND_LEVELS};
+mod fallback;
+pub use fallback::{Fallback, FallbackCell};
+mod segregate;
+pub use segregate::{Segregate, SegregatedCell};
+mod limited;
+pub use limited::Limited;
+mod zeroizing;
+pub use zeroizing::{Zeroizing, ZeroizingCell};
+mod thread_cache;
+pub use thread_cache::{ThreadCache, THREAD_CACHE_SIZE_CLASSES};
+mod global_pool;
+pub use global_pool::{GlobalPool, GlobalCell, GlobalFreelist};
+mod mapped_file_pool;
+#[cfg(all(not(feature="no-stdlib"), target_os="linux",
+          any(target_arch="x86", target_arch="x86_64", target_arch="arm", target_arch="aarch64",
+              target_arch="riscv64")))]
+pub use mapped_file_pool::{MappedFilePool, PersistentAllocator, MAPPED_POOL_MAGIC, MAPPED_POOL_VERSION};
+mod offset_freelist;
+pub use offset_freelist::{FreeRegion, OffsetFreelist, OffsetStackAllocator};
+mod shared_pool;
+#[cfg(all(not(feature="no-stdlib"), target_os="linux",
+          any(target_arch="x86", target_arch="x86_64", target_arch="arm", target_arch="aarch64",
+              target_arch="riscv64")))]
+pub use shared_pool::{SharedMemoryPool, SharedPoolAllocator, SHARED_POOL_MAGIC, SHARED_POOL_VERSION};
+mod allocator_set;
+pub use allocator_set::{AllocatorSet, split_typed_pool};
+mod pod;
+pub use pod::{Pod, Zeroable};
+mod typed_stack_allocator;
+pub use typed_stack_allocator::{TypedStackAllocator, alloc_typed_cell, try_alloc_typed_cell, free_typed_cell, TYPED_CELL_GRANULE};
 use core::default::Default;
 pub fn bzero<T : Default> (data : &mut [T]) {
     for iter in data.iter_mut() {
@@ -31,8 +83,20 @@ pub fn bzero<T : Default> (data : &mut [T]) {
 
 pub fn uninitialized<T> (_data : &mut[T]) {}
 
+// bzero for memory that held secrets: volatile writes plus a fence, so the optimizer cannot
+// drop the wipe as a dead store after the last use of the data
+pub fn secure_zero<T : Zeroable> (data : &mut [T]) {
+    let bytes = data.as_mut_ptr() as *mut u8;
+    for index in 0..core::mem::size_of_val(data) {
+        unsafe { core::ptr::write_volatile(bytes.add(index), 0) };
+    }
+    core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+}
 
 
+
+/// Pool memory from calloc/malloc. Dropping it frees the memory (if asked to) without running
+/// any item's destructor: for types with Drop, empty the cells with free_cell_dropping first.
 #[derive(Debug)]
 pub struct CallocBackingStore<'a, T : 'a> {
     pub raw_data : *mut u8,
@@ -40,6 +104,12 @@ pub struct CallocBackingStore<'a, T : 'a> {
     free : unsafe extern "C" fn(*mut u8),
 }
 
+mod locked_backing_store;
+#[cfg(all(target_os="linux",
+          any(target_arch="x86", target_arch="x86_64", target_arch="arm", target_arch="aarch64",
+              target_arch="riscv64")))]
+pub use locked_backing_store::LockedBackingStore;
+
 pub enum AllocatorC {
    Calloc(unsafe extern "C" fn(usize, usize) -> *mut u8),
    Malloc(unsafe extern "C" fn(usize) -> *mut u8),
@@ -0,0 +1,75 @@
+extern crate core;
+use super::allocated_memory::SliceWrapper;
+use super::stack_allocator::Allocator;
+
+/// Caps the number of items live in cells from inner at limit. Cells are counted at their
+/// actual length, which for StackAllocator may exceed the request. Past the cap try_alloc_cell
+/// returns None and alloc_cell panics.
+pub struct Limited<A> {
+    pub inner : A,
+    pub limit : usize,
+    live : usize,
+}
+
+impl<A> Limited<A> {
+    pub fn new(inner : A, limit : usize) -> Self {
+        return Limited::<A>{
+            inner : inner,
+            limit : limit,
+            live : 0,
+        };
+    }
+    // the total length of the cells handed out and not yet freed
+    pub fn live(self : &Self) -> usize {
+        return self.live;
+    }
+    pub fn remaining(self : &Self) -> usize {
+        return self.limit.saturating_sub(self.live);
+    }
+    pub fn into_inner(self) -> A {
+        return self.inner;
+    }
+    fn fits(self : &Self, len : usize) -> bool {
+        return len <= self.remaining();
+    }
+}
+
+impl<T, A : Allocator<T>> Allocator<T> for Limited<A> {
+    type AllocatedMemory = A::AllocatedMemory;
+    fn alloc_cell(self : &mut Self, len : usize) -> A::AllocatedMemory {
+        if !self.fits(len) {
+            panic!("allocation of {} items exceeds the limit of {} live items ({} in use)",
+                   len, self.limit, self.live);
+        }
+        let cell = self.inner.alloc_cell(len);
+        let cell_len = cell.slice().len();
+        if !self.fits(cell_len) {
+            self.inner.free_cell(cell);
+            panic!("allocation of {} items exceeds the limit of {} live items ({} in use)",
+                   cell_len, self.limit, self.live);
+        }
+        self.live += cell_len;
+        return cell;
+    }
+    fn try_alloc_cell(self : &mut Self, len : usize) -> Option<A::AllocatedMemory> {
+        if !self.fits(len) {
+            return None;
+        }
+        let cell = match self.inner.try_alloc_cell(len) {
+            Some(cell) => cell,
+            None => return None,
+        };
+        let cell_len = cell.slice().len();
+        if !self.fits(cell_len) {
+            self.inner.free_cell(cell);
+            return None;
+        }
+        self.live += cell_len;
+        return Some(cell);
+    }
+    // a cell that was never counted (foreign or grown by re-slicing) cannot take live below zero
+    fn free_cell(self : &mut Self, data : A::AllocatedMemory) {
+        self.live = self.live.saturating_sub(data.slice().len());
+        self.inner.free_cell(data);
+    }
+}
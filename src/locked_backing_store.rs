@@ -0,0 +1,93 @@
+#![cfg(all(target_os="linux",
+           any(target_arch="x86", target_arch="x86_64", target_arch="arm", target_arch="aarch64",
+               target_arch="riscv64")))]
+extern crate core;
+use super::pod::Zeroable;
+use super::secure_zero;
+
+const PROT_READ : i32 = 1;
+const PROT_WRITE : i32 = 2;
+const MAP_PRIVATE : i32 = 2;
+const MAP_ANONYMOUS : i32 = 0x20;
+const MADV_DONTDUMP : i32 = 16;
+
+// the C off_t mmap takes, which is only 64 bits wide on 64-bit targets
+#[allow(non_camel_case_types)]
+#[cfg(target_pointer_width="64")]
+pub type off_t = i64;
+#[allow(non_camel_case_types)]
+#[cfg(target_pointer_width="32")]
+pub type off_t = i32;
+
+extern "C" {
+    fn __errno_location() -> *mut i32;
+    fn mmap(addr : *mut u8, len : usize, prot : i32, flags : i32, fd : i32, offset : off_t) -> *mut u8;
+    fn munmap(addr : *mut u8, len : usize) -> i32;
+    fn mlock(addr : *const u8, len : usize) -> i32;
+    fn munlock(addr : *const u8, len : usize) -> i32;
+    fn madvise(addr : *mut u8, len : usize, advice : i32) -> i32;
+}
+
+/// Pool memory mapped privately, locked into RAM with mlock and excluded from core dumps, for
+/// arenas holding credentials. Dropping it wipes the pool with secure_zero before it is unlocked
+/// and unmapped. Like CallocBackingStore it never runs an item's destructor.
+pub struct LockedBackingStore<'a, T : Zeroable + 'a> {
+    raw_data : *mut u8,
+    raw_len : usize,
+    pub data : &'a mut [T],
+}
+
+impl<'a, T : Zeroable + 'a> LockedBackingStore<'a, T> {
+    /// None if the mapping fails or cannot be locked, typically because of RLIMIT_MEMLOCK; errno
+    /// is left as the failing call set it.
+    ///
+    /// # Safety
+    ///
+    /// As with CallocBackingStore::new, 'a is not tied to the store: data must not be used after
+    /// the store is dropped.
+    pub unsafe fn new(num_elements : usize) -> Option<Self> {
+        let raw_len = match num_elements.checked_mul(core::mem::size_of::<T>()) {
+            Some(0) => 1,
+            Some(raw_len) => raw_len,
+            None => return None,
+        };
+        unsafe {
+            let raw_data = mmap(core::ptr::null_mut(), raw_len, PROT_READ | PROT_WRITE,
+                                MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+            if raw_data as usize == usize::MAX {
+                return None;
+            }
+            if mlock(raw_data, raw_len) != 0 {
+                let errno = *__errno_location();
+                munmap(raw_data, raw_len);
+                *__errno_location() = errno;
+                return None;
+            }
+            if madvise(raw_data, raw_len, MADV_DONTDUMP) != 0 {
+                let errno = *__errno_location();
+                munlock(raw_data, raw_len);
+                munmap(raw_data, raw_len);
+                *__errno_location() = errno;
+                return None;
+            }
+            // anonymous mappings start zeroed, which is a valid T
+            return Some(LockedBackingStore::<'a, T>{
+                raw_data : raw_data,
+                raw_len : raw_len,
+                data : core::slice::from_raw_parts_mut(raw_data as *mut T, num_elements),
+            });
+        }
+    }
+}
+
+impl<'a, T : Zeroable + 'a> Drop for LockedBackingStore<'a, T> {
+    fn drop(self : &mut Self) {
+        self.data = &mut [];
+        unsafe {
+            // the whole mapping, whatever became of data
+            secure_zero(core::slice::from_raw_parts_mut(self.raw_data, self.raw_len));
+            munlock(self.raw_data, self.raw_len);
+            munmap(self.raw_data, self.raw_len);
+        }
+    }
+}
@@ -0,0 +1,262 @@
+#![cfg(all(not(feature="no-stdlib"), target_os="linux",
+           any(target_arch="x86", target_arch="x86_64", target_arch="arm", target_arch="aarch64",
+               target_arch="riscv64")))]
+use core;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use super::{Allocator, AllocatedStackMemory};
+use super::locked_backing_store::off_t;
+use super::offset_freelist::{FreeRegion, OffsetFreelist, OffsetStackAllocator};
+
+pub const MAPPED_POOL_MAGIC : [u8; 8] = *b"ANSPOOL\0";
+pub const MAPPED_POOL_VERSION : u32 = 2;
+
+const PROT_READ : i32 = 1;
+const PROT_WRITE : i32 = 2;
+const MAP_SHARED : i32 = 1;
+const MS_SYNC : i32 = 4;
+
+extern "C" {
+    fn mmap(addr : *mut u8, len : usize, prot : i32, flags : i32, fd : i32, offset : off_t) -> *mut u8;
+    fn munmap(addr : *mut u8, len : usize) -> i32;
+    fn msync(addr : *mut u8, len : usize, flags : i32) -> i32;
+}
+
+// the start of every pool file, followed by the pool; all offsets are relative to the first
+// pool byte
+#[repr(C)]
+struct PoolHeader<const N : usize> {
+    magic : [u8; 8],
+    version : u32,
+    clean : u32,
+    freelist_size : u64,
+    pool_len : u64,
+    root_offset : u64,
+    root_len : u64,
+    freelist : OffsetFreelist<N>,
+}
+
+fn invalid_data(message : &str) -> io::Error {
+    return io::Error::new(io::ErrorKind::InvalidData, message);
+}
+
+/// A file mapped with MAP_SHARED as the pool of an OffsetStackAllocator<u8, N>, so tables built
+/// in it survive the process. The file holds a header (magic, version, clean flag), the
+/// OffsetFreelist itself and one root cell from which a later run finds its data. The free list
+/// uses usize offsets, so a file only opens on targets with the pointer width that wrote it.
+pub struct MappedFilePool<const N : usize> {
+    _file : File,
+    raw_data : *mut u8,
+    raw_len : usize,
+}
+
+impl<const N : usize> MappedFilePool<N> {
+    // creates or truncates path to hold a pool of pool_len bytes
+    pub fn create<P : AsRef<Path>>(path : P, pool_len : usize) -> io::Result<MappedFilePool<N>> {
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(path)?;
+        let raw_len = core::mem::size_of::<PoolHeader<N>>() + pool_len;
+        file.set_len(raw_len as u64)?;
+        let mut ret = MappedFilePool::<N>::map(file, raw_len)?;
+        unsafe {
+            core::ptr::write(ret.header(), PoolHeader::<N>{
+                magic : MAPPED_POOL_MAGIC,
+                version : MAPPED_POOL_VERSION,
+                clean : 1,
+                freelist_size : N as u64,
+                pool_len : pool_len as u64,
+                root_offset : 0,
+                root_len : 0,
+                freelist : OffsetFreelist::<N>::new(pool_len),
+            });
+        }
+        ret.flush()?;
+        return Ok(ret);
+    }
+    // maps a pool file written by create, rejecting it unless it was last closed cleanly
+    pub fn open<P : AsRef<Path>>(path : P) -> io::Result<MappedFilePool<N>> {
+        return MappedFilePool::<N>::open_checked(path, true);
+    }
+    // maps a pool file left unclean by a crashed process, as long as its free list is still
+    // consistent; cells the crashed run allocated without freeing stay allocated
+    pub fn open_recover<P : AsRef<Path>>(path : P) -> io::Result<MappedFilePool<N>> {
+        return MappedFilePool::<N>::open_checked(path, false);
+    }
+    fn open_checked<P : AsRef<Path>>(path : P, require_clean : bool) -> io::Result<MappedFilePool<N>> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        let raw_len = file.metadata()?.len() as usize;
+        if raw_len < core::mem::size_of::<PoolHeader<N>>() {
+            return Err(invalid_data("not a mapped pool file"));
+        }
+        let ret = MappedFilePool::<N>::map(file, raw_len)?;
+        ret.validate(require_clean)?;
+        return Ok(ret);
+    }
+    fn map(file : File, raw_len : usize) -> io::Result<MappedFilePool<N>> {
+        let raw_data = unsafe {
+            mmap(core::ptr::null_mut(), raw_len, PROT_READ | PROT_WRITE, MAP_SHARED, file.as_raw_fd(), 0)
+        };
+        if raw_data as usize == usize::MAX {
+            return Err(io::Error::last_os_error());
+        }
+        return Ok(MappedFilePool::<N>{_file : file, raw_data : raw_data, raw_len : raw_len});
+    }
+    fn validate(self : &Self, require_clean : bool) -> io::Result<()> {
+        let header = unsafe { &*self.header() };
+        if header.magic != MAPPED_POOL_MAGIC {
+            return Err(invalid_data("not a mapped pool file"));
+        }
+        if header.version != MAPPED_POOL_VERSION {
+            return Err(invalid_data("unsupported mapped pool version"));
+        }
+        if require_clean && header.clean != 1 {
+            return Err(invalid_data("mapped pool was not closed cleanly"));
+        }
+        if header.freelist_size != N as u64
+           || (core::mem::size_of::<PoolHeader<N>>() as u64).checked_add(header.pool_len) != Some(self.raw_len as u64) {
+            return Err(invalid_data("corrupt mapped pool header"));
+        }
+        // every free region and the root must lie in the pool without overlapping
+        let pool_len = self.pool_len() as u64;
+        if !header.freelist.fits(self.pool_len())
+           || header.root_offset.checked_add(header.root_len).map_or(true, |end| end > pool_len) {
+            return Err(invalid_data("corrupt mapped pool free list"));
+        }
+        if header.root_len != 0 {
+            for region in header.freelist.regions.iter().filter(|region| region.len != 0) {
+                if (region.offset as u64) < header.root_offset + header.root_len
+                   && header.root_offset < (region.offset + region.len) as u64 {
+                    return Err(invalid_data("corrupt mapped pool free list"));
+                }
+            }
+        }
+        return Ok(());
+    }
+    fn header(self : &Self) -> *mut PoolHeader<N> {
+        return self.raw_data as *mut PoolHeader<N>;
+    }
+    fn pool_len(self : &Self) -> usize {
+        return self.raw_len - core::mem::size_of::<PoolHeader<N>>();
+    }
+    fn flush(self : &mut Self) -> io::Result<()> {
+        if unsafe { msync(self.raw_data, self.raw_len, MS_SYNC) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        return Ok(());
+    }
+    // the allocator over the pool, working on the free list in the file
+    pub fn allocator<'a>(self : &'a mut Self, initializer : fn(&mut[u8])) -> PersistentAllocator<'a, N> {
+        let header = self.header();
+        let alloc = unsafe {
+            OffsetStackAllocator::<'a, u8, N>::from_raw_parts(self.raw_data.add(core::mem::size_of::<PoolHeader<N>>()),
+                                                              self.pool_len(), &mut (*header).freelist, initializer)
+        };
+        return PersistentAllocator::<'a, N>{
+            header : header,
+            raw_data : self.raw_data,
+            raw_len : self.raw_len,
+            root_taken : false,
+            alloc : alloc,
+        };
+    }
+}
+
+impl<const N : usize> Drop for MappedFilePool<N> {
+    fn drop(self : &mut Self) {
+        unsafe { munmap(self.raw_data, self.raw_len) };
+    }
+}
+
+/// OffsetStackAllocator over a MappedFilePool. The free list is updated in the file as cells come
+/// and go; sync (also run on drop) marks the file clean and flushes it. A run that ends without
+/// it leaves the file unclean, which open rejects and open_recover accepts.
+pub struct PersistentAllocator<'a, const N : usize> {
+    // only touched field by field, alloc holds the free list
+    header : *mut PoolHeader<N>,
+    raw_data : *mut u8,
+    raw_len : usize,
+    root_taken : bool,
+    alloc : OffsetStackAllocator<'a, u8, N>,
+}
+
+impl<'a, const N : usize> PersistentAllocator<'a, N> {
+    fn mark_dirty(self : &mut Self) {
+        unsafe { (*self.header).clean = 0 };
+    }
+    fn root(self : &Self) -> FreeRegion {
+        return unsafe {
+            FreeRegion{offset : (*self.header).root_offset as usize, len : (*self.header).root_len as usize}
+        };
+    }
+    // records cell as the root the next run gets back from take_root
+    pub fn set_root(self : &mut Self, cell : &AllocatedStackMemory<'a, u8>) {
+        let region = self.alloc.region_of(cell);
+        self.mark_dirty();
+        unsafe {
+            (*self.header).root_offset = region.offset as u64;
+            (*self.header).root_len = region.len as u64;
+        }
+        self.root_taken = true;
+    }
+    // the root cell recorded by an earlier run; only handed out once per mapping
+    pub fn take_root(self : &mut Self) -> Option<AllocatedStackMemory<'a, u8>> {
+        let root = self.root();
+        if self.root_taken || root.len == 0 {
+            return None;
+        }
+        self.root_taken = true;
+        // validated against the free list when the file was opened
+        return Some(unsafe { self.alloc.cell_at(root) });
+    }
+    // the (offset, len) handle of a cell from this pool, valid in later runs as well; store it in
+    // the root cell to find the cell again after reopening
+    pub fn handle(self : &Self, cell : &AllocatedStackMemory<'a, u8>) -> FreeRegion {
+        return self.alloc.region_of(cell);
+    }
+    /// Rebuilds the cell behind a handle made by handle, possibly in an earlier run.
+    ///
+    /// # Safety
+    ///
+    /// handle must name a cell that is still allocated and not reachable through any other live cell.
+    pub unsafe fn cell_at(self : &Self, handle : FreeRegion) -> AllocatedStackMemory<'a, u8> {
+        return self.alloc.cell_at(handle);
+    }
+    // marks the file clean and flushes it to disk
+    pub fn sync(self : &mut Self) -> io::Result<()> {
+        unsafe { (*self.header).clean = 1 };
+        if unsafe { msync(self.raw_data, self.raw_len, MS_SYNC) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        return Ok(());
+    }
+}
+
+impl<'a, const N : usize> Allocator<u8> for PersistentAllocator<'a, N> {
+    type AllocatedMemory = AllocatedStackMemory<'a, u8>;
+    fn alloc_cell(self : &mut Self, len : usize) -> AllocatedStackMemory<'a, u8> {
+        self.mark_dirty();
+        return self.alloc.alloc_cell(len);
+    }
+    fn try_alloc_cell(self : &mut Self, len : usize) -> Option<AllocatedStackMemory<'a, u8>> {
+        self.mark_dirty();
+        return self.alloc.try_alloc_cell(len);
+    }
+    fn free_cell(self : &mut Self, data : AllocatedStackMemory<'a, u8>) {
+        self.mark_dirty();
+        // a freed root would overlap the free list
+        if data.mem.len() != 0 && self.alloc.region_of(&data) == self.root() {
+            unsafe {
+                (*self.header).root_offset = 0;
+                (*self.header).root_len = 0;
+            }
+        }
+        self.alloc.free_cell(data);
+    }
+}
+
+impl<'a, const N : usize> Drop for PersistentAllocator<'a, N> {
+    fn drop(self : &mut Self) {
+        let _ = self.sync();
+    }
+}
@@ -0,0 +1,195 @@
+extern crate core;
+use core::marker::PhantomData;
+use super::allocated_stack_memory::AllocatedStackMemory;
+use super::stack_allocator::{Allocator, ZeroPolicy};
+
+/// A free run of a pool, as items from the pool start.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FreeRegion {
+    pub offset : usize,
+    pub len : usize,
+}
+
+/// StackAllocator's free list state as plain data: no references, so it can be copied,
+/// written out, placed in shared memory or moved along with a relocated pool.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OffsetFreelist<const N : usize> {
+    pub free_list_start : usize,
+    pub free_list_overflow_count : usize,
+    // one past the last item ever handed out, see ZeroPolicy::DirtyWatermark
+    pub dirty_watermark : usize,
+    pub regions : [FreeRegion; N],
+}
+
+impl<const N : usize> OffsetFreelist<N> {
+    // a free list holding the whole of a fresh pool of pool_len items
+    pub fn new(pool_len : usize) -> Self {
+        assert!(N != 0);
+        let mut regions = [FreeRegion::default(); N];
+        regions[N - 1] = FreeRegion{offset : 0, len : pool_len};
+        return OffsetFreelist::<N>{
+            free_list_start : N - 1,
+            free_list_overflow_count : 0,
+            dirty_watermark : 0,
+            regions : regions,
+        };
+    }
+    // whether the slot indices are in range and the free regions lie inside a pool of pool_len
+    // items without overlapping
+    pub fn fits(self : &Self, pool_len : usize) -> bool {
+        if self.free_list_start > N || self.free_list_overflow_count >= N || self.dirty_watermark > pool_len {
+            return false;
+        }
+        let mut regions = self.regions;
+        regions.sort_unstable_by_key(|region| region.offset);
+        let mut end = 0;
+        for region in regions.iter().filter(|region| region.len != 0) {
+            if region.offset < end || region.len > pool_len || region.offset > pool_len - region.len {
+                return false;
+            }
+            end = region.offset + region.len;
+        }
+        return true;
+    }
+}
+
+/// StackAllocator over an OffsetFreelist: the same first-fit policy and cells, but the free
+/// list records positions relative to the pool rather than borrowed slices of it.
+pub struct OffsetStackAllocator<'a, T : 'a, const N : usize> {
+    base : *mut T,
+    pool_len : usize,
+    freelist : &'a mut OffsetFreelist<N>,
+    pub initialize : fn(&mut [T]),
+    pub zero_policy : ZeroPolicy,
+    _pool : PhantomData<&'a mut [T]>,
+}
+
+unsafe impl<'a, T : Send + 'a, const N : usize> Send for OffsetStackAllocator<'a, T, N> {}
+
+impl<'a, T : 'a, const N : usize> OffsetStackAllocator<'a, T, N> {
+    // freelist may come from an earlier allocator over this pool or a copy of the pool; its free
+    // regions must lie inside the pool without overlapping
+    pub fn new(pool : &'a mut [T],
+               freelist : &'a mut OffsetFreelist<N>,
+               initializer : fn(&mut[T])) -> Self {
+        assert!(freelist.fits(pool.len()));
+        return unsafe { OffsetStackAllocator::from_raw_parts(pool.as_mut_ptr(), pool.len(), freelist, initializer) };
+    }
+    /// Builds the allocator over a pool it does not borrow, e.g. one in shared memory.
+    ///
+    /// # Safety
+    ///
+    /// base must point to pool_len items valid for 'a, and the free regions of freelist must lie
+    /// inside them without overlapping each other or any cell still in use.
+    pub unsafe fn from_raw_parts(base : *mut T,
+                                 pool_len : usize,
+                                 freelist : &'a mut OffsetFreelist<N>,
+                                 initializer : fn(&mut[T])) -> Self {
+        return OffsetStackAllocator::<'a, T, N>{
+            base : base,
+            pool_len : pool_len,
+            freelist : freelist,
+            initialize : initializer,
+            zero_policy : ZeroPolicy::DirtyWatermark,
+            _pool : PhantomData,
+        };
+    }
+    // read only: new checked the free list against the pool, and cells are built from it unchecked
+    pub fn freelist(self : &Self) -> &OffsetFreelist<N> {
+        return self.freelist;
+    }
+    // the (offset, len) of a cell from this allocator, e.g. to hand it to another mapping of the pool
+    pub fn region_of(self : &Self, cell : &AllocatedStackMemory<'a, T>) -> FreeRegion {
+        if cell.mem.len() == 0 {
+            return FreeRegion::default();
+        }
+        let offset = (cell.mem.as_ptr() as usize - self.base as usize) / core::cmp::max(core::mem::size_of::<T>(), 1);
+        assert!(offset + cell.mem.len() <= self.pool_len);
+        return FreeRegion{offset : offset, len : cell.mem.len()};
+    }
+    /// Rebuilds the cell at region, e.g. one allocated through another mapping of the pool.
+    ///
+    /// # Safety
+    ///
+    /// region must be a cell that is allocated and not reachable through any other live cell.
+    pub unsafe fn cell_at(self : &Self, region : FreeRegion) -> AllocatedStackMemory<'a, T> {
+        assert!(region.len <= self.pool_len && region.offset <= self.pool_len - region.len);
+        if region.len == 0 {
+            return AllocatedStackMemory::<'a, T>::default();
+        }
+        return AllocatedStackMemory::<'a, T>{
+            mem : core::slice::from_raw_parts_mut(self.base.add(region.offset), region.len),
+        };
+    }
+    fn clear_if_necessary(self : &mut Self, region : FreeRegion) -> AllocatedStackMemory<'a, T> {
+        let mem = unsafe { core::slice::from_raw_parts_mut(self.base.add(region.offset), region.len) };
+        let fnp = self.initialize;
+        match self.zero_policy {
+            ZeroPolicy::ZeroOnAlloc => fnp(mem),
+            ZeroPolicy::DirtyWatermark => if region.offset < self.freelist.dirty_watermark {
+                let dirty_len = core::cmp::min(region.len, self.freelist.dirty_watermark - region.offset);
+                fnp(&mut mem[..dirty_len]);
+            },
+            ZeroPolicy::ZeroOnFree | ZeroPolicy::Never => {},
+        }
+        if region.offset + region.len > self.freelist.dirty_watermark {
+            self.freelist.dirty_watermark = region.offset + region.len;
+        }
+        return AllocatedStackMemory::<'a, T>{mem : mem};
+    }
+}
+
+impl<'a, T : 'a, const N : usize> Allocator<T> for OffsetStackAllocator<'a, T, N> {
+    type AllocatedMemory = AllocatedStackMemory<'a, T>;
+    fn alloc_cell(self : &mut Self, len : usize) -> AllocatedStackMemory<'a, T> {
+        match self.try_alloc_cell(len) {
+            Some(cell) => return cell,
+            None => panic!("OOM"),
+        }
+    }
+    fn try_alloc_cell(self : &mut Self, len : usize) -> Option<AllocatedStackMemory<'a, T>> {
+        if len == 0 {
+            return Some(AllocatedStackMemory::<'a, T>::default());
+        }
+        let start = self.freelist.free_list_start;
+        let index = match self.freelist.regions[start..].iter().position(|region| region.len >= len) {
+            Some(position) => start + position,
+            None => return None,
+        };
+        let available = self.freelist.regions[index];
+        if available.len == len || (available.len < len + 32 && index + 1 != N) {
+            // we don't want really small wasted regions
+            self.freelist.regions[index] = self.freelist.regions[start];
+            self.freelist.regions[start] = FreeRegion::default();
+            self.freelist.free_list_start += 1;
+            return Some(self.clear_if_necessary(available));
+        }
+        self.freelist.regions[index] = FreeRegion{offset : available.offset + len, len : available.len - len};
+        return Some(self.clear_if_necessary(FreeRegion{offset : available.offset, len : len}));
+    }
+    fn free_cell(self : &mut Self, val : AllocatedStackMemory<'a, T>) {
+        if val.mem.len() == 0 {
+            return;
+        }
+        let region = self.region_of(&val);
+        if self.zero_policy == ZeroPolicy::ZeroOnFree {
+            let fnp = self.initialize;
+            fnp(val.mem);
+        }
+        if self.freelist.free_list_start > 0 {
+            self.freelist.free_list_start -= 1;
+            self.freelist.regions[self.freelist.free_list_start] = region;
+        } else {
+            for _i in 0..3 {
+                self.freelist.free_list_overflow_count += 1;
+                self.freelist.free_list_overflow_count %= N;
+                if self.freelist.regions[self.freelist.free_list_overflow_count].len < region.len {
+                    self.freelist.regions[self.freelist.free_list_overflow_count] = region;
+                    return;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,33 @@
+#[cfg(feature="bytemuck")]
+extern crate bytemuck;
+
+/// Types for which all-zero memory is a valid value, so cells may come straight from
+/// zeroed pages without writing every item.
+pub unsafe trait Zeroable {}
+
+/// Plain-old-data: no padding, no pointers and every bit pattern is a valid value,
+/// so a run of these may be placed directly over bytes taken from a `u8` pool.
+pub unsafe trait Pod : Zeroable + Copy + 'static {}
+
+#[cfg(feature="bytemuck")]
+unsafe impl<T : bytemuck::Zeroable> Zeroable for T {}
+
+#[cfg(feature="bytemuck")]
+unsafe impl<T : bytemuck::Pod> Pod for T {}
+
+#[cfg(not(feature="bytemuck"))]
+macro_rules! impl_pod(
+    ($($T : ty),*) => {
+        $(unsafe impl Zeroable for $T {}
+          unsafe impl Pod for $T {})*
+    };
+);
+
+#[cfg(not(feature="bytemuck"))]
+impl_pod!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+
+#[cfg(not(feature="bytemuck"))]
+unsafe impl<T : Zeroable, const N : usize> Zeroable for [T; N] {}
+
+#[cfg(not(feature="bytemuck"))]
+unsafe impl<T : Pod, const N : usize> Pod for [T; N] {}
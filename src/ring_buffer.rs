@@ -0,0 +1,141 @@
+extern crate core;
+use core::marker::PhantomData;
+use super::allocated_memory::{SliceWrapper, SliceWrapperMut};
+use super::stack_allocator::Allocator;
+
+/// Fixed-capacity circular buffer whose storage is a single cell from an `Allocator<T>`.
+pub struct AllocRingBuffer<T, A : Allocator<T>> {
+    data : A::AllocatedMemory,
+    head : usize,
+    len : usize,
+    _element : PhantomData<T>,
+}
+
+impl<T, A : Allocator<T>> AllocRingBuffer<T, A> {
+    pub fn new(allocator : &mut A, capacity : usize) -> Self {
+        return AllocRingBuffer::<T, A>{
+            data : allocator.alloc_cell(capacity),
+            head : 0,
+            len : 0,
+            _element : PhantomData,
+        };
+    }
+    pub fn capacity(self : &Self) -> usize {
+        return self.data.slice().len();
+    }
+    pub fn len(self : &Self) -> usize {
+        return self.len;
+    }
+    // the index in data of the front item
+    pub fn head(self : &Self) -> usize {
+        return self.head;
+    }
+    // the whole backing cell, including slots that hold no item
+    pub fn data(self : &Self) -> &A::AllocatedMemory {
+        return &self.data;
+    }
+    pub fn is_empty(self : &Self) -> bool {
+        return self.len == 0;
+    }
+    pub fn is_full(self : &Self) -> bool {
+        return self.len == self.capacity();
+    }
+    fn tail(self : &Self) -> usize {
+        let capacity = self.capacity();
+        if capacity == 0 {
+            return 0;
+        }
+        return (self.head + self.len) % capacity;
+    }
+    pub fn push_back(self : &mut Self, value : T) -> Result<(), T> {
+        if self.is_full() {
+            return Err(value);
+        }
+        let tail = self.tail();
+        self.data.slice_mut()[tail] = value;
+        self.len += 1;
+        return Ok(());
+    }
+    pub fn pop_front(self : &mut Self) -> Option<T> where T : Default {
+        if self.len == 0 {
+            return None;
+        }
+        let head = self.head;
+        let value = core::mem::replace(&mut self.data.slice_mut()[head], T::default());
+        self.consume(1);
+        return Some(value);
+    }
+    pub fn front(self : &Self) -> Option<&T> {
+        if self.len == 0 {
+            return None;
+        }
+        return Some(&self.data.slice()[self.head]);
+    }
+    // the readable contents in order: the second slice is non-empty only when the data wraps
+    pub fn as_slices(self : &Self) -> (&[T], &[T]) {
+        let capacity = self.capacity();
+        let data = self.data.slice();
+        if self.head + self.len <= capacity {
+            return (&data[self.head..self.head + self.len], &[]);
+        }
+        let (wrapped, first) = data.split_at(self.head);
+        return (first, &wrapped[..self.head + self.len - capacity]);
+    }
+    pub fn as_mut_slices(self : &mut Self) -> (&mut [T], &mut [T]) {
+        let capacity = self.capacity();
+        let head = self.head;
+        let len = self.len;
+        let data = self.data.slice_mut();
+        if head + len <= capacity {
+            return (&mut data[head..head + len], &mut []);
+        }
+        let (wrapped, first) = data.split_at_mut(head);
+        return (first, &mut wrapped[..head + len - capacity]);
+    }
+    // drops count items from the front, typically after reading them through as_slices
+    pub fn consume(self : &mut Self, count : usize) {
+        assert!(count <= self.len);
+        self.len -= count;
+        if self.len == 0 {
+            self.head = 0;
+        } else {
+            self.head = (self.head + count) % self.capacity();
+        }
+    }
+    // the largest contiguous free region after the last item; fill it and then call commit_write
+    pub fn contiguous_write_region(self : &mut Self) -> &mut [T] {
+        let capacity = self.capacity();
+        let tail = self.tail();
+        let end = if self.head + self.len < capacity {
+            capacity
+        } else {
+            self.head
+        };
+        if self.is_full() {
+            return &mut [];
+        }
+        return &mut self.data.slice_mut()[tail..end];
+    }
+    pub fn commit_write(self : &mut Self, count : usize) {
+        assert!(self.len + count <= self.capacity());
+        self.len += count;
+    }
+    // moves the contents into a fresh cell of new_capacity and returns the old cell to the allocator
+    pub fn resize(self : &mut Self, allocator : &mut A, new_capacity : usize) {
+        assert!(new_capacity >= self.len);
+        let mut new_data = allocator.alloc_cell(new_capacity);
+        {
+            let (first, second) = self.as_mut_slices();
+            let dst = new_data.slice_mut();
+            for (index, item) in first.iter_mut().chain(second.iter_mut()).enumerate() {
+                core::mem::swap(&mut dst[index], item);
+            }
+        }
+        let old_data = core::mem::replace(&mut self.data, new_data);
+        allocator.free_cell(old_data);
+        self.head = 0;
+    }
+    pub fn free(self : Self, allocator : &mut A) {
+        allocator.free_cell(self.data);
+    }
+}
@@ -0,0 +1,96 @@
+extern crate core;
+use core::ops;
+use super::allocated_memory::{SliceWrapper, SliceWrapperMut};
+use super::stack_allocator::Allocator;
+
+/// A cell from a `Segregate` allocator, tagged with the backend that produced it.
+pub enum SegregatedCell<A, B> {
+    Small(A),
+    Large(B),
+}
+
+impl<A : Default, B> Default for SegregatedCell<A, B> {
+    fn default() -> Self {
+        return SegregatedCell::Small(A::default());
+    }
+}
+
+impl<T, A : SliceWrapper<T>, B : SliceWrapper<T>> SliceWrapper<T> for SegregatedCell<A, B> {
+    fn slice(& self) -> & [T] {
+        match *self {
+            SegregatedCell::Small(ref cell) => return cell.slice(),
+            SegregatedCell::Large(ref cell) => return cell.slice(),
+        }
+    }
+}
+
+impl<T, A : SliceWrapperMut<T>, B : SliceWrapperMut<T>> SliceWrapperMut<T> for SegregatedCell<A, B> {
+    fn slice_mut(& mut self) -> & mut [T] {
+        match *self {
+            SegregatedCell::Small(ref mut cell) => return cell.slice_mut(),
+            SegregatedCell::Large(ref mut cell) => return cell.slice_mut(),
+        }
+    }
+}
+
+impl<A : ops::Index<usize>, B : ops::Index<usize, Output = A::Output>> ops::Index<usize> for SegregatedCell<A, B> {
+    type Output = A::Output;
+    fn index(&self, index : usize) -> &A::Output {
+        match *self {
+            SegregatedCell::Small(ref cell) => return &cell[index],
+            SegregatedCell::Large(ref cell) => return &cell[index],
+        }
+    }
+}
+
+impl<A : ops::IndexMut<usize>, B : ops::IndexMut<usize, Output = A::Output>> ops::IndexMut<usize> for SegregatedCell<A, B> {
+    fn index_mut(&mut self, index : usize) -> &mut A::Output {
+        match *self {
+            SegregatedCell::Small(ref mut cell) => return &mut cell[index],
+            SegregatedCell::Large(ref mut cell) => return &mut cell[index],
+        }
+    }
+}
+
+/// Sends requests shorter than THRESHOLD items to small (e.g. a SlabAllocator or a small stack
+/// pool) and everything else to large (e.g. HeapPrealloc or HeapAlloc). free_cell returns each
+/// cell to the backend that produced it.
+pub struct Segregate<const THRESHOLD : usize, Small, Large> {
+    pub small : Small,
+    pub large : Large,
+}
+
+impl<const THRESHOLD : usize, Small, Large> Segregate<THRESHOLD, Small, Large> {
+    pub fn new(small : Small, large : Large) -> Self {
+        return Segregate::<THRESHOLD, Small, Large>{
+            small : small,
+            large : large,
+        };
+    }
+    pub fn into_inner(self) -> (Small, Large) {
+        return (self.small, self.large);
+    }
+}
+
+impl<T, const THRESHOLD : usize, Small : Allocator<T>, Large : Allocator<T>> Allocator<T> for Segregate<THRESHOLD, Small, Large>
+    where Large::AllocatedMemory : ops::IndexMut<usize, Output = <Small::AllocatedMemory as ops::Index<usize>>::Output> {
+    type AllocatedMemory = SegregatedCell<Small::AllocatedMemory, Large::AllocatedMemory>;
+    fn alloc_cell(self : &mut Self, len : usize) -> Self::AllocatedMemory {
+        if len < THRESHOLD {
+            return SegregatedCell::Small(self.small.alloc_cell(len));
+        }
+        return SegregatedCell::Large(self.large.alloc_cell(len));
+    }
+    fn try_alloc_cell(self : &mut Self, len : usize) -> Option<Self::AllocatedMemory> {
+        if len < THRESHOLD {
+            return self.small.try_alloc_cell(len).map(SegregatedCell::Small);
+        }
+        return self.large.try_alloc_cell(len).map(SegregatedCell::Large);
+    }
+    fn free_cell(self : &mut Self, data : Self::AllocatedMemory) {
+        match data {
+            SegregatedCell::Small(cell) => self.small.free_cell(cell),
+            SegregatedCell::Large(cell) => self.large.free_cell(cell),
+        }
+    }
+}
@@ -0,0 +1,240 @@
+#![cfg(all(not(feature="no-stdlib"), target_os="linux",
+           any(target_arch="x86", target_arch="x86_64", target_arch="arm", target_arch="aarch64",
+               target_arch="riscv64")))]
+use core;
+use core::sync::atomic::{AtomicU32, Ordering};
+use std::ffi::CString;
+use std::fs::File;
+use std::io;
+use std::os::raw::c_char;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use super::{Allocator, AllocatedStackMemory, ZeroPolicy};
+use super::locked_backing_store::off_t;
+use super::offset_freelist::{FreeRegion, OffsetFreelist, OffsetStackAllocator};
+
+pub const SHARED_POOL_MAGIC : [u8; 8] = *b"ANSSHM\0\0";
+pub const SHARED_POOL_VERSION : u32 = 1;
+
+const PROT_READ : i32 = 1;
+const PROT_WRITE : i32 = 2;
+const MAP_SHARED : i32 = 1;
+const MFD_CLOEXEC : u32 = 1;
+
+extern "C" {
+    fn memfd_create(name : *const c_char, flags : u32) -> i32;
+    fn mmap(addr : *mut u8, len : usize, prot : i32, flags : i32, fd : i32, offset : off_t) -> *mut u8;
+    fn munmap(addr : *mut u8, len : usize) -> i32;
+}
+
+// the start of the shared object, followed by the pool; the lock guards the free list
+#[repr(C)]
+struct SharedPoolHeader<const N : usize> {
+    magic : [u8; 8],
+    version : u32,
+    lock : AtomicU32,
+    freelist_size : u64,
+    pool_len : u64,
+    freelist : OffsetFreelist<N>,
+}
+
+fn invalid_data(message : &str) -> io::Error {
+    return io::Error::new(io::ErrorKind::InvalidData, message);
+}
+
+/// A u8 pool in a shared memory object that several processes map at once. The free list
+/// lives in the object as an OffsetFreelist<N> behind a spinlock, so every process allocates
+/// from and frees into the same pool, and cells travel between processes as FreeRegion handles.
+pub struct SharedMemoryPool<const N : usize> {
+    file : File,
+    raw_data : *mut u8,
+    raw_len : usize,
+}
+
+unsafe impl<const N : usize> Send for SharedMemoryPool<N> {}
+unsafe impl<const N : usize> Sync for SharedMemoryPool<N> {}
+
+impl<const N : usize> SharedMemoryPool<N> {
+    // a new memfd holding a pool of pool_len bytes; name only shows up in /proc/<pid>/fd
+    pub fn create(name : &str, pool_len : usize) -> io::Result<SharedMemoryPool<N>> {
+        let name = match CString::new(name) {
+            Ok(name) => name,
+            Err(_) => return Err(io::Error::new(io::ErrorKind::InvalidInput, "name contains a nul byte")),
+        };
+        let fd = unsafe { memfd_create(name.as_ptr(), MFD_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let file = unsafe { File::from_raw_fd(fd) };
+        let raw_len = core::mem::size_of::<SharedPoolHeader<N>>() + pool_len;
+        file.set_len(raw_len as u64)?;
+        let ret = SharedMemoryPool::<N>::map(file, raw_len)?;
+        // no other process can see the object yet
+        unsafe {
+            core::ptr::write(ret.header(), SharedPoolHeader::<N>{
+                magic : SHARED_POOL_MAGIC,
+                version : SHARED_POOL_VERSION,
+                lock : AtomicU32::new(0),
+                freelist_size : N as u64,
+                pool_len : pool_len as u64,
+                freelist : OffsetFreelist::<N>::new(pool_len),
+            });
+        }
+        return Ok(ret);
+    }
+    // maps a pool created by another process, e.g. a memfd inherited across fork or received over
+    // a unix socket, or a file opened from /proc/<pid>/fd/<n> or /dev/shm
+    pub fn from_file(file : File) -> io::Result<SharedMemoryPool<N>> {
+        let raw_len = file.metadata()?.len() as usize;
+        if raw_len < core::mem::size_of::<SharedPoolHeader<N>>() {
+            return Err(invalid_data("not a shared pool"));
+        }
+        let ret = SharedMemoryPool::<N>::map(file, raw_len)?;
+        let header = unsafe { &*ret.header() };
+        if header.magic != SHARED_POOL_MAGIC {
+            return Err(invalid_data("not a shared pool"));
+        }
+        if header.version != SHARED_POOL_VERSION {
+            return Err(invalid_data("unsupported shared pool version"));
+        }
+        if header.freelist_size != N as u64
+           || (core::mem::size_of::<SharedPoolHeader<N>>() as u64).checked_add(header.pool_len) != Some(raw_len as u64) {
+            return Err(invalid_data("shared pool has a different layout"));
+        }
+        let fits = {
+            let _guard = ret.lock();
+            header.freelist.fits(ret.pool_len())
+        };
+        if !fits {
+            return Err(invalid_data("corrupt shared pool free list"));
+        }
+        return Ok(ret);
+    }
+    fn map(file : File, raw_len : usize) -> io::Result<SharedMemoryPool<N>> {
+        let raw_data = unsafe {
+            mmap(core::ptr::null_mut(), raw_len, PROT_READ | PROT_WRITE, MAP_SHARED, file.as_raw_fd(), 0)
+        };
+        if raw_data as usize == usize::MAX {
+            return Err(io::Error::last_os_error());
+        }
+        return Ok(SharedMemoryPool::<N>{file : file, raw_data : raw_data, raw_len : raw_len});
+    }
+    fn lock(self : &Self) -> SharedPoolLockGuard<'_> {
+        let lock = unsafe { &(*self.header()).lock };
+        while lock.compare_exchange_weak(0, 1, Ordering::Acquire, Ordering::Relaxed).is_err() {
+            while lock.load(Ordering::Relaxed) != 0 {
+                core::hint::spin_loop();
+            }
+        }
+        return SharedPoolLockGuard{lock : lock};
+    }
+    fn header(self : &Self) -> *mut SharedPoolHeader<N> {
+        return self.raw_data as *mut SharedPoolHeader<N>;
+    }
+    fn base(self : &Self) -> *mut u8 {
+        return unsafe { self.raw_data.add(core::mem::size_of::<SharedPoolHeader<N>>()) };
+    }
+    #[doc(hidden)]
+    // byte offset of regions[index].len in the shared object, for tests that corrupt the free
+    // list; addr_of! on an uninit header stands in for offset_of!, which needs rustc 1.77
+    pub fn region_len_offset(index : usize) -> usize {
+        assert!(index < N);
+        let header = core::mem::MaybeUninit::<SharedPoolHeader<N>>::uninit();
+        let start = header.as_ptr();
+        let len = unsafe { core::ptr::addr_of!((*start).freelist.regions[index].len) };
+        return len as usize - start as usize;
+    }
+    // the shared object, to pass to another process
+    pub fn file(self : &Self) -> &File {
+        return &self.file;
+    }
+    pub fn pool_len(self : &Self) -> usize {
+        return self.raw_len - core::mem::size_of::<SharedPoolHeader<N>>();
+    }
+    pub fn allocator<'a>(self : &'a Self, initializer : fn(&mut[u8])) -> SharedPoolAllocator<'a, N> {
+        return SharedPoolAllocator::<'a, N>{
+            pool : self,
+            initialize : initializer,
+            zero_policy : ZeroPolicy::DirtyWatermark,
+        };
+    }
+}
+
+impl<const N : usize> Drop for SharedMemoryPool<N> {
+    fn drop(self : &mut Self) {
+        unsafe { munmap(self.raw_data, self.raw_len) };
+    }
+}
+
+// releases the in-pool lock, also when the initializer panics
+struct SharedPoolLockGuard<'b> {
+    lock : &'b AtomicU32,
+}
+
+impl<'b> Drop for SharedPoolLockGuard<'b> {
+    fn drop(&mut self) {
+        self.lock.store(0, Ordering::Release);
+    }
+}
+
+/// One process's (or thread's) view of a SharedMemoryPool. Every call takes the in-pool
+/// spinlock, so a process that dies inside alloc_cell or free_cell leaves the pool locked.
+pub struct SharedPoolAllocator<'a, const N : usize> {
+    pool : &'a SharedMemoryPool<N>,
+    pub initialize : fn(&mut [u8]),
+    pub zero_policy : ZeroPolicy,
+}
+
+impl<'a, const N : usize> SharedPoolAllocator<'a, N> {
+    fn with_lock<R, F : FnOnce(&mut OffsetStackAllocator<'a, u8, N>) -> R>(self : &mut Self, f : F) -> R {
+        let header = self.pool.header();
+        let _guard = self.pool.lock();
+        // another process may have written anything into the free list since it was last checked
+        assert!(unsafe { (*header).freelist.fits(self.pool.pool_len()) }, "corrupt shared pool free list");
+        let mut alloc = unsafe {
+            OffsetStackAllocator::<'a, u8, N>::from_raw_parts(self.pool.base(), self.pool.pool_len(),
+                                                              &mut (*header).freelist, self.initialize)
+        };
+        alloc.zero_policy = self.zero_policy;
+        return f(&mut alloc);
+    }
+    // the (offset, len) handle of a cell from this pool, valid in every process mapping it
+    pub fn handle(self : &Self, cell : &AllocatedStackMemory<'a, u8>) -> FreeRegion {
+        if cell.mem.len() == 0 {
+            return FreeRegion::default();
+        }
+        let offset = cell.mem.as_ptr() as usize - self.pool.base() as usize;
+        assert!(offset + cell.mem.len() <= self.pool.pool_len());
+        return FreeRegion{offset : offset, len : cell.mem.len()};
+    }
+    /// Rebuilds the cell behind a handle made by handle, possibly in another process.
+    ///
+    /// # Safety
+    ///
+    /// handle must name a cell that is allocated and that no other process or cell still uses.
+    pub unsafe fn cell_at(self : &Self, handle : FreeRegion) -> AllocatedStackMemory<'a, u8> {
+        let pool_len = self.pool.pool_len();
+        assert!(handle.len <= pool_len && handle.offset <= pool_len - handle.len);
+        if handle.len == 0 {
+            return AllocatedStackMemory::<'a, u8>::default();
+        }
+        return AllocatedStackMemory::<'a, u8>{
+            mem : core::slice::from_raw_parts_mut(self.pool.base().add(handle.offset), handle.len),
+        };
+    }
+}
+
+impl<'a, const N : usize> Allocator<u8> for SharedPoolAllocator<'a, N> {
+    type AllocatedMemory = AllocatedStackMemory<'a, u8>;
+    fn alloc_cell(self : &mut Self, len : usize) -> AllocatedStackMemory<'a, u8> {
+        match self.try_alloc_cell(len) {
+            Some(cell) => return cell,
+            None => panic!("OOM"),
+        }
+    }
+    fn try_alloc_cell(self : &mut Self, len : usize) -> Option<AllocatedStackMemory<'a, u8>> {
+        return self.with_lock(|alloc| alloc.try_alloc_cell(len));
+    }
+    fn free_cell(self : &mut Self, data : AllocatedStackMemory<'a, u8>) {
+        self.with_lock(|alloc| alloc.free_cell(data));
+    }
+}
@@ -0,0 +1,101 @@
+extern crate core;
+use core::marker::PhantomData;
+use super::allocated_memory;
+use super::allocated_stack_memory::AllocatedStackMemory;
+use super::stack_allocator::Allocator;
+
+/// Carves a pool into cells of a few fixed sizes, with one free stack per size class, so
+/// alloc_cell and free_cell cost O(CLASSES) regardless of how many cells are outstanding.
+/// Cells come back with the full length of their class, which may exceed the request.
+pub struct SlabAllocator<'a, T : 'a, U : allocated_memory::AllocatedSlice<&'a mut [T]>, const CLASSES : usize> {
+    pub slabs : U,
+    pub class_len : [usize; CLASSES],
+    pub class_start : [usize; CLASSES],
+    pub class_count : [usize; CLASSES],
+    pub class_free : [usize; CLASSES],
+    pub initialize : fn(&mut [T]),
+    _cells : PhantomData<&'a mut [T]>,
+}
+
+impl<'a, T : 'a, U : allocated_memory::AllocatedSlice<&'a mut [T]>, const CLASSES : usize>
+    SlabAllocator<'a, T, U, CLASSES> {
+    // classes lists (cell length, cell count) in ascending order of cell length; slabs needs
+    // at least one slot per cell and pool at least the sum of length * count items
+    pub fn new(mut pool : &'a mut [T],
+               mut slabs : U,
+               classes : [(usize, usize); CLASSES],
+               initializer : fn(&mut[T])) -> Self {
+        let mut class_len = [0; CLASSES];
+        let mut class_start = [0; CLASSES];
+        let mut class_count = [0; CLASSES];
+        let mut slot = 0;
+        for (index, &(cell_len, count)) in classes.iter().enumerate() {
+            assert!(cell_len != 0 && (index == 0 || classes[index - 1].0 < cell_len));
+            class_len[index] = cell_len;
+            class_start[index] = slot;
+            class_count[index] = count;
+            for _i in 0..count {
+                if pool.len() < cell_len || slot == slabs.slice().len() {
+                    panic!("OOM");
+                }
+                let (cell, remainder) = core::mem::replace(&mut pool, &mut []).split_at_mut(cell_len);
+                pool = remainder;
+                slabs.slice_mut()[slot] = cell;
+                slot += 1;
+            }
+        }
+        return SlabAllocator::<'a, T, U, CLASSES>{
+            slabs : slabs,
+            class_len : class_len,
+            class_start : class_start,
+            class_count : class_count,
+            class_free : class_count,
+            initialize : initializer,
+            _cells : PhantomData,
+        };
+    }
+}
+
+impl<'a, T : 'a, U : allocated_memory::AllocatedSlice<&'a mut [T]>, const CLASSES : usize>
+    Allocator<T> for SlabAllocator<'a, T, U, CLASSES> {
+    type AllocatedMemory = AllocatedStackMemory<'a, T>;
+    // uses the smallest class that fits and still has a free cell
+    fn alloc_cell(self : &mut Self, len : usize) -> AllocatedStackMemory<'a, T> {
+        match self.try_alloc_cell(len) {
+            Some(cell) => return cell,
+            None => panic!("OOM"),
+        }
+    }
+    fn try_alloc_cell(self : &mut Self, len : usize) -> Option<AllocatedStackMemory<'a, T>> {
+        if len == 0 {
+            return Some(AllocatedStackMemory::<'a, T>::default());
+        }
+        for class in 0..CLASSES {
+            if self.class_len[class] >= len && self.class_free[class] != 0 {
+                self.class_free[class] -= 1;
+                let slot = self.class_start[class] + self.class_free[class];
+                let cell = core::mem::replace(&mut self.slabs.slice_mut()[slot], &mut []);
+                let fnp = self.initialize;
+                fnp(cell);
+                return Some(AllocatedStackMemory::<'a, T>{mem : cell});
+            }
+        }
+        return None;
+    }
+    // a cell whose length matches no class with room to spare did not come from this allocator
+    fn free_cell(self : &mut Self, val : AllocatedStackMemory<'a, T>) {
+        let len = val.mem.len();
+        if len == 0 {
+            return;
+        }
+        for class in 0..CLASSES {
+            if self.class_len[class] == len && self.class_free[class] < self.class_count[class] {
+                let slot = self.class_start[class] + self.class_free[class];
+                self.slabs.slice_mut()[slot] = val.mem;
+                self.class_free[class] += 1;
+                return;
+            }
+        }
+        debug_assert!(false, "cell was not allocated from this SlabAllocator");
+    }
+}
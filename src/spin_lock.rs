@@ -0,0 +1,52 @@
+#![cfg(feature="no-stdlib")]
+extern crate core;
+use core::cell::UnsafeCell;
+use core::ops;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+pub struct SpinLock<T> {
+    locked : AtomicBool,
+    data : UnsafeCell<T>,
+}
+
+unsafe impl<T : Send> Sync for SpinLock<T> {}
+
+pub struct SpinLockGuard<'b, T : 'b> {
+    lock : &'b SpinLock<T>,
+}
+
+impl<T> SpinLock<T> {
+    pub fn new(data : T) -> Self {
+        return SpinLock::<T>{locked : AtomicBool::new(false), data : UnsafeCell::new(data)};
+    }
+    pub fn lock(self : &Self) -> SpinLockGuard<'_, T> {
+        while self.locked.compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
+            while self.locked.load(Ordering::Relaxed) {
+                core::hint::spin_loop();
+            }
+        }
+        return SpinLockGuard::<T>{lock : self};
+    }
+    pub fn into_inner(self) -> T {
+        return self.data.into_inner();
+    }
+}
+
+impl<'b, T : 'b> ops::Deref for SpinLockGuard<'b, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        return unsafe {&*self.lock.data.get()};
+    }
+}
+
+impl<'b, T : 'b> ops::DerefMut for SpinLockGuard<'b, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        return unsafe {&mut *self.lock.data.get()};
+    }
+}
+
+impl<'b, T : 'b> Drop for SpinLockGuard<'b, T> {
+    fn drop(&mut self) {
+        self.lock.locked.store(false, Ordering::Release);
+    }
+}
@@ -2,14 +2,37 @@ extern crate core;
 use super::allocated_memory;
 use super::allocated_stack_memory::AllocatedStackMemory;
 use super::SliceWrapper;
+use super::offset_freelist::{FreeRegion, OffsetFreelist};
 
 pub trait Allocator<T> {
     type AllocatedMemory : allocated_memory::AllocatedSlice<T>;
     fn alloc_cell(&mut self, len : usize) -> Self::AllocatedMemory;
+    // returns None where alloc_cell would panic; allocators that can't tell ahead of time keep this default
+    fn try_alloc_cell(&mut self, len : usize) -> Option<Self::AllocatedMemory> {
+        return Some(self.alloc_cell(len));
+    }
     fn free_cell(&mut self, data : Self::AllocatedMemory);
 }
 
+/// When a StackAllocator runs its initialize function over cell contents.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ZeroPolicy {
+    // over every cell alloc_cell returns
+    ZeroOnAlloc,
+    // over every cell passed to free_cell, scrubbing it as soon as it is released; fresh pool
+    // memory is assumed to be initialized already
+    ZeroOnFree,
+    // over the part of each returned cell below dirty_watermark, so pool memory that was never
+    // handed out (e.g. straight from calloc) is not initialized a second time
+    DirtyWatermark,
+    Never,
+}
 
+/// Hands out cells carved from borrowed slices of a pool. Items are moved around by slice, never
+/// by value, so their destructors run only when something overwrites them: the initializer of a
+/// later alloc_cell (bzero assigns T::default() and so drops the old value), free_cell_dropping,
+/// or the owner of the pool dropping it. uninitialized runs none, so values left in freed cells
+/// live until the pool itself goes away, and never for a CallocBackingStore pool.
 pub struct StackAllocator<'a,
                            T :'a,
                            U : allocated_memory::AllocatedSlice<&'a mut [T]>> {
@@ -18,15 +41,94 @@ pub struct StackAllocator<'a,
     pub free_list_start : usize,
     pub free_list_overflow_count : usize,
     pub initialize : fn(&mut[T]),
+    pub zero_policy : ZeroPolicy,
+    // one past the highest address ever handed out: memory at or above it is still as the pool
+    // was given to the allocator
+    pub dirty_watermark : usize,
+    // the address and length of the pool the free list was carved from, for snapshot and restore
+    pub pool_start : usize,
+    pub pool_len : usize,
 }
 
 impl <'a, T : 'a, U : allocated_memory::AllocatedSlice<&'a mut[T]> >
      StackAllocator <'a, T, U> {
-    fn clear_if_necessary(self : &Self, index : usize, data : AllocatedStackMemory<'a, T>)
+    // drops every item of the cell right away by resetting it to T::default(), then frees it
+    pub fn free_cell_dropping(self : &mut Self, val : AllocatedStackMemory<'a, T>) where T : Default {
+        super::bzero(val.mem);
+        self.free_cell(val);
+    }
+    // the free list as offsets from the pool start, to reinstate later with restore; N must be the
+    // free list length. Free slices outside the pool (foreign cells given to free_cell) cannot be
+    // named by an offset and are left out, so restore drops them from the free list
+    pub fn snapshot<const N : usize>(self : &Self) -> OffsetFreelist<N> {
+        let item_size = core::cmp::max(core::mem::size_of::<T>(), 1);
+        let slots = self.system_resources.slice();
+        assert_eq!(slots.len(), N);
+        let mut regions = [FreeRegion::default(); N];
+        for (region, slot) in regions.iter_mut().zip(slots.iter()) {
+            let start = slot.as_ptr() as usize;
+            if slot.len() != 0 && start >= self.pool_start && (start - self.pool_start) % item_size == 0 {
+                let offset = (start - self.pool_start) / item_size;
+                if offset <= self.pool_len && slot.len() <= self.pool_len - offset {
+                    *region = FreeRegion{offset : offset, len : slot.len()};
+                }
+            }
+        }
+        let dirty_watermark = if self.dirty_watermark > self.pool_start {
+            core::cmp::min((self.dirty_watermark - self.pool_start) / item_size, self.pool_len)
+        } else {
+            0
+        };
+        return OffsetFreelist::<N>{
+            free_list_start : self.free_list_start,
+            free_list_overflow_count : self.free_list_overflow_count,
+            dirty_watermark : dirty_watermark,
+            regions : regions,
+        };
+    }
+    /// Replaces the free list with one taken by snapshot over the same pool, e.g. to reproduce a
+    /// fragmentation state in a test.
+    ///
+    /// # Safety
+    ///
+    /// No cell still in use may overlap a free region of snapshot, e.g. restore right after
+    /// new_allocator over the pool.
+    pub unsafe fn restore<const N : usize>(self : &mut Self, snapshot : &OffsetFreelist<N>) {
+        let item_size = core::cmp::max(core::mem::size_of::<T>(), 1);
+        assert_eq!(self.system_resources.slice().len(), N);
+        assert!(snapshot.fits(self.pool_len));
+        let pool = self.pool_start as *mut T;
+        for (slot, region) in self.system_resources.slice_mut().iter_mut().zip(snapshot.regions.iter()) {
+            *slot = if region.len == 0 {
+                &mut []
+            } else {
+                core::slice::from_raw_parts_mut(pool.add(region.offset), region.len)
+            };
+        }
+        self.free_list_start = snapshot.free_list_start;
+        self.free_list_overflow_count = snapshot.free_list_overflow_count;
+        // memory dirtied since the snapshot was taken still needs initializing
+        if snapshot.dirty_watermark != 0 {
+            self.dirty_watermark = core::cmp::max(self.dirty_watermark,
+                                                  self.pool_start + snapshot.dirty_watermark * item_size);
+        }
+    }
+    fn clear_if_necessary(self : &mut Self, data : AllocatedStackMemory<'a, T>)
     -> AllocatedStackMemory<'a, T> {
-        if index + 1 != self.system_resources.slice().len() {
-            let fnp = self.initialize;
-            fnp(data.mem);
+        let item_size = core::cmp::max(core::mem::size_of::<T>(), 1);
+        let start = data.mem.as_ptr() as usize;
+        let end = start + data.mem.len() * item_size;
+        let fnp = self.initialize;
+        match self.zero_policy {
+            ZeroPolicy::ZeroOnAlloc => fnp(data.mem),
+            ZeroPolicy::DirtyWatermark => if start < self.dirty_watermark {
+                let dirty_len = (core::cmp::min(end, self.dirty_watermark) - start) / item_size;
+                fnp(&mut data.mem[..dirty_len]);
+            },
+            ZeroPolicy::ZeroOnFree | ZeroPolicy::Never => {},
+        }
+        if end > self.dirty_watermark {
+            self.dirty_watermark = end;
         }
         return data;
     }
@@ -36,8 +138,15 @@ impl<'a, T : 'a, U : allocated_memory::AllocatedSlice<&'a mut[T]> >
     type AllocatedMemory = AllocatedStackMemory<'a, T>;
     fn alloc_cell(self : &mut StackAllocator<'a, T, U>,
                   len : usize) -> AllocatedStackMemory<'a, T> {
+        match self.try_alloc_cell(len) {
+            Some(cell) => return cell,
+            None => panic!("OOM"),
+        }
+    }
+    fn try_alloc_cell(self : &mut StackAllocator<'a, T, U>,
+                      len : usize) -> Option<AllocatedStackMemory<'a, T>> {
         if len == 0 {
-            return AllocatedStackMemory::<'a, T>::default();
+            return Some(AllocatedStackMemory::<'a, T>::default());
         }
         let mut index : usize = self.free_list_start;
         let mut found : bool = false;
@@ -49,7 +158,7 @@ impl<'a, T : 'a, U : allocated_memory::AllocatedSlice<&'a mut[T]> >
             index += 1;
         }
         if !found {
-            panic!("OOM");
+            return None;
         }
         let mut available_slice = core::mem::replace(&mut self.system_resources.slice_mut()[index],
                                                     &mut[]);
@@ -67,12 +176,11 @@ impl<'a, T : 'a, U : allocated_memory::AllocatedSlice<&'a mut[T]> >
                                    farthest_free_list);
             }
             self.free_list_start += 1;
-            return self.clear_if_necessary(index,
-                                           AllocatedStackMemory::<'a, T>{mem:available_slice});
+            return Some(self.clear_if_necessary(AllocatedStackMemory::<'a, T>{mem:available_slice}));
         } else { // the memory allocated was not the entire range of items. Split and move on
             let (mut retval, return_to_sender) = available_slice.split_at_mut(len);
             core::mem::replace(&mut self.system_resources.slice_mut()[index], return_to_sender);
-            return self.clear_if_necessary(index, AllocatedStackMemory::<'a, T>{mem:retval});
+            return Some(self.clear_if_necessary(AllocatedStackMemory::<'a, T>{mem:retval}));
         }
     }
     fn free_cell(self : &mut StackAllocator<'a, T, U>,
@@ -80,6 +188,10 @@ impl<'a, T : 'a, U : allocated_memory::AllocatedSlice<&'a mut[T]> >
         if val.slice().len() == 0 {
             return;
         }
+        if self.zero_policy == ZeroPolicy::ZeroOnFree {
+            let fnp = self.initialize;
+            fnp(val.mem);
+        }
         if self.free_list_start > 0 {
             self.free_list_start -=1;
             core::mem::replace(&mut self.system_resources.slice_mut()[self.free_list_start],
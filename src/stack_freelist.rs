@@ -0,0 +1,62 @@
+extern crate core;
+use core::ops;
+use super::allocated_memory::{SliceWrapper, SliceWrapperMut};
+use super::allocated_stack_memory::AllocatedStackMemory;
+use super::stack_allocator::{Allocator, StackAllocator, ZeroPolicy};
+
+/// Free list of N slots held inline, usable as the `system_resources` of a StackAllocator.
+#[derive(Debug)]
+pub struct StackFreelist<'a, T : 'a, const N : usize> {
+    pub freelist : [&'a mut [T]; N],
+}
+
+impl<'a, T : 'a, const N : usize> Default for StackFreelist<'a, T, N> {
+    fn default() -> Self {
+        return StackFreelist::<'a, T, N>{freelist : core::array::from_fn(|_| &mut [][..])};
+    }
+}
+
+impl<'a, T : 'a, const N : usize> SliceWrapper<&'a mut [T]> for StackFreelist<'a, T, N> {
+    fn slice(& self) -> & [&'a mut [T]] {
+        return & self.freelist;
+    }
+}
+
+impl<'a, T : 'a, const N : usize> SliceWrapperMut<&'a mut [T]> for StackFreelist<'a, T, N> {
+    fn slice_mut(& mut self) -> &mut [&'a mut [T]] {
+        return &mut self.freelist;
+    }
+}
+
+impl<'a, T : 'a, const N : usize> ops::Index<usize> for StackFreelist<'a, T, N> {
+    type Output = [T];
+    fn index<'b>(&'b self, _index : usize) -> &'b [T] {
+        return &self.freelist[_index];
+    }
+}
+
+impl<'a, T : 'a, const N : usize> ops::IndexMut<usize> for StackFreelist<'a, T, N> {
+    fn index_mut<'b>(&'b mut self, _index : usize) -> &'b mut [T] {
+        return &mut self.freelist[_index];
+    }
+}
+
+impl<'a, T : 'a, const N : usize> StackFreelist<'a, T, N> {
+    pub fn new_allocator(global_buffer : &'a mut [T],
+                         initializer : fn(&mut[T])) -> StackAllocator<'a, T, StackFreelist<'a, T, N> > {
+        let mut retval = StackAllocator::<T, StackFreelist<T, N> > {
+            nop : &mut [],
+            system_resources : StackFreelist::<T, N>::default(),
+            free_list_start : N,
+            free_list_overflow_count : 0,
+            initialize : initializer,
+            zero_policy : ZeroPolicy::DirtyWatermark,
+            dirty_watermark : 0,
+            pool_start : global_buffer.as_ptr() as usize,
+            pool_len : global_buffer.len(),
+        };
+        retval.free_cell(AllocatedStackMemory::<T>{mem : global_buffer});
+        return retval;
+    }
+}
+
@@ -0,0 +1,85 @@
+extern crate core;
+use core::ops;
+#[cfg(not(feature="no-stdlib"))]
+use std::sync::Mutex;
+#[cfg(feature="no-stdlib")]
+use super::spin_lock::SpinLock;
+use super::allocated_memory;
+use super::allocated_stack_memory::AllocatedStackMemory;
+use super::stack_allocator::{Allocator, StackAllocator};
+
+/// A StackAllocator behind a lock (std::sync::Mutex, or a spinlock without the stdlib)
+/// so that one pool may serve several threads through cloned handles.
+pub struct SyncStackAllocator<'a, T : 'a, U : allocated_memory::AllocatedSlice<&'a mut [T]> > {
+    #[cfg(not(feature="no-stdlib"))]
+    alloc : Mutex<StackAllocator<'a, T, U> >,
+    #[cfg(feature="no-stdlib")]
+    alloc : SpinLock<StackAllocator<'a, T, U> >,
+}
+
+impl<'a, T : 'a, U : allocated_memory::AllocatedSlice<&'a mut [T]> > SyncStackAllocator<'a, T, U> {
+    #[cfg(not(feature="no-stdlib"))]
+    pub fn new(alloc : StackAllocator<'a, T, U>) -> Self {
+        return SyncStackAllocator::<'a, T, U>{alloc : Mutex::new(alloc)};
+    }
+    #[cfg(feature="no-stdlib")]
+    pub fn new(alloc : StackAllocator<'a, T, U>) -> Self {
+        return SyncStackAllocator::<'a, T, U>{alloc : SpinLock::new(alloc)};
+    }
+    // the allocator state stays consistent if a thread panics while holding the lock (e.g. on OOM)
+    #[cfg(not(feature="no-stdlib"))]
+    pub fn lock(self : &Self) -> impl ops::DerefMut<Target = StackAllocator<'a, T, U> > + '_ {
+        return self.alloc.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    }
+    #[cfg(feature="no-stdlib")]
+    pub fn lock(self : &Self) -> impl ops::DerefMut<Target = StackAllocator<'a, T, U> > + '_ {
+        return self.alloc.lock();
+    }
+    #[cfg(not(feature="no-stdlib"))]
+    pub fn into_inner(self) -> StackAllocator<'a, T, U> {
+        return self.alloc.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner());
+    }
+    #[cfg(feature="no-stdlib")]
+    pub fn into_inner(self) -> StackAllocator<'a, T, U> {
+        return self.alloc.into_inner();
+    }
+    pub fn handle(self : &Self) -> SyncStackAllocatorHandle<'_, Self> {
+        return SyncStackAllocatorHandle::<Self>{shared : self};
+    }
+}
+
+/// A cheap, copyable reference to a SyncStackAllocator that implements `Allocator<T>`.
+pub struct SyncStackAllocatorHandle<'b, S : 'b> {
+    shared : &'b S,
+}
+
+impl<'b, S : 'b> Clone for SyncStackAllocatorHandle<'b, S> {
+    fn clone(&self) -> Self {
+        return *self;
+    }
+}
+
+impl<'b, S : 'b> Copy for SyncStackAllocatorHandle<'b, S> {}
+
+impl<'b, 'a : 'b, T : 'a, U : allocated_memory::AllocatedSlice<&'a mut [T]> >
+    Allocator<T> for SyncStackAllocatorHandle<'b, SyncStackAllocator<'a, T, U> > {
+    type AllocatedMemory = AllocatedStackMemory<'a, T>;
+    fn alloc_cell(self : &mut Self, len : usize) -> AllocatedStackMemory<'a, T> {
+        if len == 0 {
+            return AllocatedStackMemory::<'a, T>::default();
+        }
+        return self.shared.lock().alloc_cell(len);
+    }
+    fn try_alloc_cell(self : &mut Self, len : usize) -> Option<AllocatedStackMemory<'a, T>> {
+        if len == 0 {
+            return Some(AllocatedStackMemory::<'a, T>::default());
+        }
+        return self.shared.lock().try_alloc_cell(len);
+    }
+    fn free_cell(self : &mut Self, data : AllocatedStackMemory<'a, T>) {
+        if data.mem.len() == 0 {
+            return;
+        }
+        self.shared.lock().free_cell(data);
+    }
+}
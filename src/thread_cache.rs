@@ -0,0 +1,116 @@
+extern crate core;
+use super::allocated_memory::{SliceWrapper, SliceWrapperMut};
+use super::stack_allocator::Allocator;
+
+// cells whose length is 2^THREAD_CACHE_SIZE_CLASSES or more always go straight to the backing allocator
+pub const THREAD_CACHE_SIZE_CLASSES : usize = 32;
+
+fn floor_log2(len : usize) -> usize {
+    return (usize::BITS - 1 - len.leading_zeros()) as usize;
+}
+
+/// A per-thread stash of recently freed cells in front of a shared allocator (usually a
+/// SyncStackAllocator handle). Up to DEPTH cells are kept for each power-of-two size class;
+/// the backing allocator is only used on a miss, when a class is full, or by flush.
+pub struct ThreadCache<T, A : Allocator<T>, const DEPTH : usize> {
+    pub inner : A,
+    magazines : [[A::AllocatedMemory; DEPTH]; THREAD_CACHE_SIZE_CLASSES],
+    counts : [usize; THREAD_CACHE_SIZE_CLASSES],
+    pub initialize : fn(&mut [T]),
+}
+
+impl<T, A : Allocator<T>, const DEPTH : usize> ThreadCache<T, A, DEPTH> {
+    pub fn new(inner : A, initializer : fn(&mut [T])) -> Self {
+        return ThreadCache::<T, A, DEPTH>{
+            inner : inner,
+            magazines : core::array::from_fn(|_| core::array::from_fn(|_| A::AllocatedMemory::default())),
+            counts : [0; THREAD_CACHE_SIZE_CLASSES],
+            initialize : initializer,
+        };
+    }
+    pub fn cached_cells(self : &Self) -> usize {
+        return self.counts.iter().sum();
+    }
+    // pops the most recently cached cell of this class if it holds at least len items
+    fn take(self : &mut Self, class : usize, len : usize) -> Option<A::AllocatedMemory> {
+        if class >= THREAD_CACHE_SIZE_CLASSES || self.counts[class] == 0 {
+            return None;
+        }
+        let top = self.counts[class] - 1;
+        if self.magazines[class][top].slice().len() < len {
+            return None;
+        }
+        self.counts[class] = top;
+        let mut cell = core::mem::replace(&mut self.magazines[class][top], A::AllocatedMemory::default());
+        let fnp = self.initialize;
+        fnp(cell.slice_mut());
+        return Some(cell);
+    }
+    // tries the class of len, then the next class up when len is not a power of two
+    fn take_cached(self : &mut Self, len : usize) -> Option<A::AllocatedMemory> {
+        if len == 0 {
+            return Some(A::AllocatedMemory::default());
+        }
+        let class = floor_log2(len);
+        if let Some(cell) = self.take(class, len) {
+            return Some(cell);
+        }
+        if !len.is_power_of_two() {
+            return self.take(class + 1, len);
+        }
+        return None;
+    }
+    // returns every cached cell to the backing allocator; also run on drop
+    pub fn flush(self : &mut Self) {
+        for class in 0..THREAD_CACHE_SIZE_CLASSES {
+            while self.counts[class] != 0 {
+                self.counts[class] -= 1;
+                let cell = core::mem::replace(&mut self.magazines[class][self.counts[class]],
+                                              A::AllocatedMemory::default());
+                self.inner.free_cell(cell);
+            }
+        }
+    }
+    pub fn into_inner(mut self) -> A {
+        self.flush();
+        // the magazines only hold empty cells after flush
+        let inner = unsafe { core::ptr::read(&self.inner) };
+        core::mem::forget(self);
+        return inner;
+    }
+}
+
+impl<T, A : Allocator<T>, const DEPTH : usize> Drop for ThreadCache<T, A, DEPTH> {
+    fn drop(self : &mut Self) {
+        self.flush();
+    }
+}
+
+impl<T, A : Allocator<T>, const DEPTH : usize> Allocator<T> for ThreadCache<T, A, DEPTH> {
+    type AllocatedMemory = A::AllocatedMemory;
+    fn alloc_cell(self : &mut Self, len : usize) -> A::AllocatedMemory {
+        if let Some(cell) = self.take_cached(len) {
+            return cell;
+        }
+        return self.inner.alloc_cell(len);
+    }
+    fn try_alloc_cell(self : &mut Self, len : usize) -> Option<A::AllocatedMemory> {
+        if let Some(cell) = self.take_cached(len) {
+            return Some(cell);
+        }
+        return self.inner.try_alloc_cell(len);
+    }
+    fn free_cell(self : &mut Self, data : A::AllocatedMemory) {
+        let len = data.slice().len();
+        if len == 0 {
+            return;
+        }
+        let class = floor_log2(len);
+        if class < THREAD_CACHE_SIZE_CLASSES && self.counts[class] < DEPTH {
+            self.magazines[class][self.counts[class]] = data;
+            self.counts[class] += 1;
+            return;
+        }
+        self.inner.free_cell(data);
+    }
+}
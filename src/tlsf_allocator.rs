@@ -0,0 +1,248 @@
+extern crate core;
+use core::marker::PhantomData;
+use super::allocated_stack_memory::AllocatedStackMemory;
+use super::stack_allocator::Allocator;
+
+pub const TLSF_FIRST_LEVELS : usize = 32;
+pub const TLSF_SECOND_LEVELS : usize = 16;
+const SECOND_LEVEL_LOG2 : usize = 4;
+const NONE : usize = usize::MAX;
+
+/// Boundary tag for one granule of a `TlsfAllocator` pool. Only the tag at the first granule of
+/// each block is meaningful; the rest are scratch space the caller provides up front.
+#[derive(Clone, Copy, Debug)]
+pub struct TlsfTag {
+    size : usize,
+    // the length of the cell handed out from an allocated block
+    cell_len : usize,
+    prev_phys : usize,
+    next_free : usize,
+    prev_free : usize,
+    free : bool,
+}
+
+impl Default for TlsfTag {
+    fn default() -> Self {
+        return TlsfTag{size : 0, cell_len : 0, prev_phys : NONE, next_free : NONE, prev_free : NONE, free : false};
+    }
+}
+
+/// Two-level segregated fit allocator over a borrowed pool, for callers that need a bounded
+/// worst case rather than StackAllocator's linear scan.
+///
+/// The pool is handed out in blocks of whole granules. Free blocks sit on one of
+/// TLSF_FIRST_LEVELS x TLSF_SECOND_LEVELS lists indexed by two bitmaps, and their boundary tags
+/// live in a caller-provided side table with one `TlsfTag` per granule, so T needs no header.
+///
+/// Worst-case bounds, independent of pool size and of the number of live cells:
+/// * alloc_cell: two find-first-set operations, one list unlink and at most one block split,
+///   plus the initializer over the returned cell.
+/// * free_cell: at most two merges with the physical neighbours and one list insert.
+/// * A request is rounded up to its granule count and then to the next second-level class,
+///   so internal waste beyond the granule rounding is below 1/TLSF_SECOND_LEVELS of the block.
+pub struct TlsfAllocator<'a, T : 'a> {
+    base : *mut T,
+    granule : usize,
+    granules : usize,
+    tags : &'a mut [TlsfTag],
+    first_level_bitmap : u32,
+    second_level_bitmap : [u16; TLSF_FIRST_LEVELS],
+    heads : [[usize; TLSF_SECOND_LEVELS]; TLSF_FIRST_LEVELS],
+    pub initialize : fn(&mut [T]),
+    _pool : PhantomData<&'a mut [T]>,
+}
+
+unsafe impl<'a, T : Send + 'a> Send for TlsfAllocator<'a, T> {}
+
+fn floor_log2(val : usize) -> usize {
+    return (usize::BITS - 1 - val.leading_zeros()) as usize;
+}
+
+// the (first level, second level) list holding free blocks of size granules
+fn mapping_insert(size : usize) -> (usize, usize) {
+    if size < TLSF_SECOND_LEVELS {
+        return (0, size);
+    }
+    let log2 = floor_log2(size);
+    let second = (size >> (log2 - SECOND_LEVEL_LOG2)) ^ TLSF_SECOND_LEVELS;
+    return (log2 - SECOND_LEVEL_LOG2 + 1, second);
+}
+
+// the first list whose every block is at least size granules long
+fn mapping_search(size : usize) -> (usize, usize) {
+    if size < TLSF_SECOND_LEVELS {
+        return (0, size);
+    }
+    let round = (1usize << (floor_log2(size) - SECOND_LEVEL_LOG2)) - 1;
+    return mapping_insert(size + round);
+}
+
+impl<'a, T : 'a> TlsfAllocator<'a, T> {
+    // tags needs one entry per whole granule of the pool; any partial granule at the end is unused
+    pub fn new(pool : &'a mut [T],
+               granule : usize,
+               tags : &'a mut [TlsfTag],
+               initializer : fn(&mut[T])) -> Self {
+        assert!(core::mem::size_of::<T>() != 0);
+        assert!(granule != 0);
+        let granules = pool.len() / granule;
+        assert!(tags.len() >= granules);
+        let mut ret = TlsfAllocator::<'a, T>{
+            base : pool.as_mut_ptr(),
+            granule : granule,
+            granules : granules,
+            tags : tags,
+            first_level_bitmap : 0,
+            second_level_bitmap : [0; TLSF_FIRST_LEVELS],
+            heads : [[NONE; TLSF_SECOND_LEVELS]; TLSF_FIRST_LEVELS],
+            initialize : initializer,
+            _pool : PhantomData,
+        };
+        if granules != 0 {
+            assert!(mapping_insert(granules).0 < TLSF_FIRST_LEVELS);
+            ret.tags[0] = TlsfTag{size : granules, .. TlsfTag::default()};
+            ret.insert_free(0);
+        }
+        return ret;
+    }
+    fn insert_free(self : &mut Self, block : usize) {
+        let (first, second) = mapping_insert(self.tags[block].size);
+        let head = self.heads[first][second];
+        self.tags[block].free = true;
+        self.tags[block].prev_free = NONE;
+        self.tags[block].next_free = head;
+        if head != NONE {
+            self.tags[head].prev_free = block;
+        }
+        self.heads[first][second] = block;
+        self.first_level_bitmap |= 1 << first;
+        self.second_level_bitmap[first] |= 1 << second;
+    }
+    fn remove_free(self : &mut Self, block : usize) {
+        let (first, second) = mapping_insert(self.tags[block].size);
+        let TlsfTag{prev_free, next_free, ..} = self.tags[block];
+        if prev_free != NONE {
+            self.tags[prev_free].next_free = next_free;
+        } else {
+            self.heads[first][second] = next_free;
+            if next_free == NONE {
+                self.second_level_bitmap[first] &= !(1 << second);
+                if self.second_level_bitmap[first] == 0 {
+                    self.first_level_bitmap &= !(1 << first);
+                }
+            }
+        }
+        if next_free != NONE {
+            self.tags[next_free].prev_free = prev_free;
+        }
+        self.tags[block].free = false;
+    }
+    fn find_suitable(self : &Self, size : usize) -> Option<usize> {
+        let (mut first, second) = mapping_search(size);
+        if first >= TLSF_FIRST_LEVELS {
+            return None;
+        }
+        let mut second_map = self.second_level_bitmap[first] as u32 & (!0u32 << second);
+        if second_map == 0 {
+            let first_map = if first + 1 < TLSF_FIRST_LEVELS {
+                self.first_level_bitmap & (!0u32 << (first + 1))
+            } else {
+                0
+            };
+            if first_map == 0 {
+                return None;
+            }
+            first = first_map.trailing_zeros() as usize;
+            second_map = self.second_level_bitmap[first] as u32;
+        }
+        return Some(self.heads[first][second_map.trailing_zeros() as usize]);
+    }
+    fn set_prev_phys_of_next(self : &mut Self, block : usize) {
+        let next = block + self.tags[block].size;
+        if next < self.granules {
+            self.tags[next].prev_phys = block;
+        }
+    }
+    fn is_free_block(self : &Self, block : usize) -> bool {
+        return block < self.granules && self.tags[block].free;
+    }
+    // the number of items in the largest free block
+    pub fn largest_free_block(self : &Self) -> usize {
+        if self.first_level_bitmap == 0 {
+            return 0;
+        }
+        let first = (u32::BITS - 1 - self.first_level_bitmap.leading_zeros()) as usize;
+        let mut block = self.heads[first][floor_log2(self.second_level_bitmap[first] as usize)];
+        let mut largest = 0;
+        while block != NONE {
+            largest = core::cmp::max(largest, self.tags[block].size);
+            block = self.tags[block].next_free;
+        }
+        return largest * self.granule;
+    }
+}
+
+impl<'a, T : 'a> Allocator<T> for TlsfAllocator<'a, T> {
+    type AllocatedMemory = AllocatedStackMemory<'a, T>;
+    fn alloc_cell(self : &mut Self, len : usize) -> AllocatedStackMemory<'a, T> {
+        match self.try_alloc_cell(len) {
+            Some(cell) => return cell,
+            None => panic!("OOM"),
+        }
+    }
+    fn try_alloc_cell(self : &mut Self, len : usize) -> Option<AllocatedStackMemory<'a, T>> {
+        if len == 0 {
+            return Some(AllocatedStackMemory::<'a, T>::default());
+        }
+        let size = (len - 1) / self.granule + 1;
+        let block = match self.find_suitable(size) {
+            Some(block) => block,
+            None => return None,
+        };
+        self.remove_free(block);
+        let block_size = self.tags[block].size;
+        if block_size > size {
+            let remainder = block + size;
+            self.tags[block].size = size;
+            self.tags[remainder] = TlsfTag{size : block_size - size, prev_phys : block, .. TlsfTag::default()};
+            self.set_prev_phys_of_next(remainder);
+            self.insert_free(remainder);
+        }
+        self.tags[block].cell_len = len;
+        let cell = unsafe {
+            core::slice::from_raw_parts_mut(self.base.add(block * self.granule), len)
+        };
+        let fnp = self.initialize;
+        fnp(cell);
+        return Some(AllocatedStackMemory::<'a, T>{mem : cell});
+    }
+    fn free_cell(self : &mut Self, val : AllocatedStackMemory<'a, T>) {
+        if val.mem.len() == 0 {
+            return;
+        }
+        // only a whole cell from alloc_cell may free its block: part of one would free the rest too
+        let byte_offset = (val.mem.as_ptr() as usize).wrapping_sub(self.base as usize);
+        let offset = byte_offset / core::mem::size_of::<T>();
+        let mut block = offset / self.granule;
+        assert!(offset < self.granules * self.granule && offset % self.granule == 0
+                && self.tags[block].size != 0 && !self.tags[block].free
+                && self.tags[block].cell_len == val.mem.len(),
+                "cell was not allocated from this TlsfAllocator");
+        self.tags[block].cell_len = 0;
+        let next = block + self.tags[block].size;
+        if self.is_free_block(next) {
+            self.remove_free(next);
+            self.tags[block].size += self.tags[next].size;
+            self.tags[next].size = 0;
+        }
+        let prev = self.tags[block].prev_phys;
+        if prev != NONE && self.tags[prev].free {
+            self.remove_free(prev);
+            self.tags[prev].size += self.tags[block].size;
+            self.tags[block].size = 0;
+            block = prev;
+        }
+        self.set_prev_phys_of_next(block);
+        self.insert_free(block);
+    }
+}
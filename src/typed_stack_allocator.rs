@@ -0,0 +1,108 @@
+extern crate core;
+use core::marker::PhantomData;
+use super::allocated_memory;
+use super::allocated_stack_memory::AllocatedStackMemory;
+use super::stack_allocator::{Allocator, StackAllocator};
+use super::pod::Pod;
+
+// The alignment allocator sets give the start of their byte pool, so that cells of most
+// element types need no padding.
+pub const TYPED_CELL_GRANULE : usize = 16;
+
+// Takes len * size_of::<T>() bytes from the byte allocator. If those bytes are not suitably
+// aligned they are returned and a run with up to align_of::<T>() - 1 bytes of slack is taken
+// instead. Whatever the byte allocator hands out before the aligned start or past the cell goes
+// straight back to it, so the cell covers exactly the bytes that were kept.
+pub fn try_alloc_typed_cell<'a, T : Pod, U : allocated_memory::AllocatedSlice<&'a mut [u8]> >(
+    bytes : &mut StackAllocator<'a, u8, U>,
+    len : usize) -> Option<AllocatedStackMemory<'a, T>> {
+    let size = core::mem::size_of::<T>();
+    if len == 0 || size == 0 {
+        let dangling = core::ptr::NonNull::<T>::dangling().as_ptr();
+        return Some(AllocatedStackMemory::<'a, T>{
+            mem : unsafe {core::slice::from_raw_parts_mut(dangling, if size == 0 {len} else {0})},
+        });
+    }
+    let align = core::mem::align_of::<T>();
+    let byte_len = match len.checked_mul(size) {
+        Some(byte_len) => byte_len,
+        None => return None,
+    };
+    let mut raw = match bytes.try_alloc_cell(byte_len) {
+        Some(cell) => cell.mem,
+        None => return None,
+    };
+    if raw.as_ptr().align_offset(align) != 0 {
+        bytes.free_cell(AllocatedStackMemory::<'a, u8>{mem : raw});
+        let padded_len = match byte_len.checked_add(align - 1) {
+            Some(padded_len) => padded_len,
+            None => return None,
+        };
+        raw = match bytes.try_alloc_cell(padded_len) {
+            Some(cell) => cell.mem,
+            None => return None,
+        };
+    }
+    let pad = raw.as_ptr().align_offset(align);
+    let (head, rest) = raw.split_at_mut(pad);
+    let (typed_bytes, tail) = rest.split_at_mut(byte_len);
+    bytes.free_cell(AllocatedStackMemory::<'a, u8>{mem : head});
+    bytes.free_cell(AllocatedStackMemory::<'a, u8>{mem : tail});
+    return Some(AllocatedStackMemory::<'a, T>{
+        mem : unsafe {core::slice::from_raw_parts_mut(typed_bytes.as_mut_ptr() as *mut T, len)},
+    });
+}
+
+pub fn alloc_typed_cell<'a, T : Pod, U : allocated_memory::AllocatedSlice<&'a mut [u8]> >(
+    bytes : &mut StackAllocator<'a, u8, U>,
+    len : usize) -> AllocatedStackMemory<'a, T> {
+    match try_alloc_typed_cell(bytes, len) {
+        Some(cell) => return cell,
+        None => panic!("OOM"),
+    }
+}
+
+// returns exactly the bytes the cell covers
+pub fn free_typed_cell<'a, T : Pod, U : allocated_memory::AllocatedSlice<&'a mut [u8]> >(
+    bytes : &mut StackAllocator<'a, u8, U>,
+    data : AllocatedStackMemory<'a, T>) {
+    if data.mem.len() == 0 || core::mem::size_of::<T>() == 0 {
+        return;
+    }
+    let byte_len = data.mem.len() * core::mem::size_of::<T>();
+    let raw = unsafe {core::slice::from_raw_parts_mut(data.mem.as_mut_ptr() as *mut u8, byte_len)};
+    bytes.free_cell(AllocatedStackMemory::<'a, u8>{mem : raw});
+}
+
+/// Hands out aligned `T` cells carved from a `u8` StackAllocator and returns their bytes on free.
+pub struct TypedStackAllocator<'a, T : Pod, U : allocated_memory::AllocatedSlice<&'a mut [u8]> > {
+    pub bytes : StackAllocator<'a, u8, U>,
+    _element : PhantomData<T>,
+}
+
+impl<'a, T : Pod, U : allocated_memory::AllocatedSlice<&'a mut [u8]> > TypedStackAllocator<'a, T, U> {
+    pub fn new(bytes : StackAllocator<'a, u8, U>) -> Self {
+        return TypedStackAllocator::<'a, T, U>{bytes : bytes, _element : PhantomData};
+    }
+    // cells handed out as one type may be freed through the view of another
+    pub fn retype<V : Pod>(self) -> TypedStackAllocator<'a, V, U> {
+        return TypedStackAllocator::<'a, V, U>::new(self.bytes);
+    }
+    pub fn into_inner(self) -> StackAllocator<'a, u8, U> {
+        return self.bytes;
+    }
+}
+
+impl<'a, T : Pod, U : allocated_memory::AllocatedSlice<&'a mut [u8]> >
+    Allocator<T> for TypedStackAllocator<'a, T, U> {
+    type AllocatedMemory = AllocatedStackMemory<'a, T>;
+    fn alloc_cell(self : &mut Self, len : usize) -> AllocatedStackMemory<'a, T> {
+        return alloc_typed_cell(&mut self.bytes, len);
+    }
+    fn try_alloc_cell(self : &mut Self, len : usize) -> Option<AllocatedStackMemory<'a, T>> {
+        return try_alloc_typed_cell(&mut self.bytes, len);
+    }
+    fn free_cell(self : &mut Self, data : AllocatedStackMemory<'a, T>) {
+        free_typed_cell(&mut self.bytes, data);
+    }
+}
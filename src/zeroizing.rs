@@ -0,0 +1,97 @@
+use core::marker::PhantomData;
+use core::ops;
+use super::allocated_memory::{SliceWrapper, SliceWrapperMut};
+use super::pod::Zeroable;
+use super::secure_zero;
+use super::stack_allocator::Allocator;
+
+/// A cell from a `Zeroizing` allocator. Its items are wiped with secure_zero when it is freed,
+/// and also when it is dropped without being freed, e.g. a HeapAlloc box going out of scope.
+pub struct ZeroizingCell<T : Zeroable, M : SliceWrapperMut<T>> {
+    mem : M,
+    _element : PhantomData<T>,
+}
+
+impl<T : Zeroable, M : SliceWrapperMut<T>> ZeroizingCell<T, M> {
+    pub fn new(mem : M) -> Self {
+        return ZeroizingCell::<T, M>{mem : mem, _element : PhantomData};
+    }
+}
+
+impl<T : Zeroable, M : SliceWrapperMut<T> + Default> ZeroizingCell<T, M> {
+    // wipes the cell and hands back the memory it wrapped
+    pub fn into_wiped(mut self) -> M {
+        secure_zero(self.mem.slice_mut());
+        return core::mem::replace(&mut self.mem, M::default());
+    }
+}
+
+impl<T : Zeroable, M : SliceWrapperMut<T> + Default> Default for ZeroizingCell<T, M> {
+    fn default() -> Self {
+        return ZeroizingCell::<T, M>::new(M::default());
+    }
+}
+
+impl<T : Zeroable, M : SliceWrapperMut<T> + SliceWrapper<T>> SliceWrapper<T> for ZeroizingCell<T, M> {
+    fn slice(& self) -> & [T] {
+        return self.mem.slice();
+    }
+}
+
+impl<T : Zeroable, M : SliceWrapperMut<T>> SliceWrapperMut<T> for ZeroizingCell<T, M> {
+    fn slice_mut(& mut self) -> & mut [T] {
+        return self.mem.slice_mut();
+    }
+}
+
+impl<T : Zeroable, M : SliceWrapperMut<T> + SliceWrapper<T>> ops::Index<usize> for ZeroizingCell<T, M> {
+    type Output = T;
+    fn index(&self, index : usize) -> &T {
+        return &self.mem.slice()[index];
+    }
+}
+
+impl<T : Zeroable, M : SliceWrapperMut<T> + SliceWrapper<T>> ops::IndexMut<usize> for ZeroizingCell<T, M> {
+    fn index_mut(&mut self, index : usize) -> &mut T {
+        return &mut self.mem.slice_mut()[index];
+    }
+}
+
+impl<T : Zeroable, M : SliceWrapperMut<T>> Drop for ZeroizingCell<T, M> {
+    fn drop(&mut self) {
+        secure_zero(self.mem.slice_mut());
+    }
+}
+
+/// Wipes every cell with secure_zero as it is freed, before inner sees it. Works around any
+/// allocator, including HeapAlloc whose freed boxes go straight back to the system allocator.
+/// For a bare StackAllocator the same effect comes from ZeroPolicy::ZeroOnFree with secure_zero
+/// as its initializer.
+pub struct Zeroizing<A> {
+    pub inner : A,
+}
+
+impl<A> Zeroizing<A> {
+    pub fn new(inner : A) -> Self {
+        return Zeroizing::<A>{inner : inner};
+    }
+    pub fn into_inner(self) -> A {
+        return self.inner;
+    }
+}
+
+impl<T : Zeroable, A : Allocator<T>> Allocator<T> for Zeroizing<A> {
+    type AllocatedMemory = ZeroizingCell<T, A::AllocatedMemory>;
+    fn alloc_cell(self : &mut Self, len : usize) -> Self::AllocatedMemory {
+        return ZeroizingCell::new(self.inner.alloc_cell(len));
+    }
+    fn try_alloc_cell(self : &mut Self, len : usize) -> Option<Self::AllocatedMemory> {
+        return match self.inner.try_alloc_cell(len) {
+            Some(cell) => Some(ZeroizingCell::new(cell)),
+            None => None,
+        };
+    }
+    fn free_cell(self : &mut Self, data : Self::AllocatedMemory) {
+        self.inner.free_cell(data.into_wiped());
+    }
+}
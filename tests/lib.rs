@@ -11,22 +11,81 @@ extern crate core;
 use core::ops;
 use alloc_no_stdlib::{Allocator, SliceWrapperMut, SliceWrapper,
             StackAllocator, AllocatedStackMemory, uninitialized, bzero};
+use alloc_no_stdlib::ZeroPolicy;
+use alloc_no_stdlib::{Zeroizing, secure_zero};
+use alloc_no_stdlib::AllocRingBuffer;
+use alloc_no_stdlib::AllocatorSet;
+use alloc_no_stdlib::TypedStackAllocator;
+use alloc_no_stdlib::StackFreelist;
+use alloc_no_stdlib::SyncStackAllocator;
+use alloc_no_stdlib::ThreadCache;
+use alloc_no_stdlib::SlabAllocator;
+use alloc_no_stdlib::BuddyAllocator;
+use alloc_no_stdlib::BumpAllocator;
+use alloc_no_stdlib::{TlsfAllocator, TlsfTag};
+use alloc_no_stdlib::{Fallback, FallbackCell};
+use alloc_no_stdlib::{Segregate, SegregatedCell};
+use alloc_no_stdlib::Limited;
+use alloc_no_stdlib::{FreeRegion, OffsetFreelist, OffsetStackAllocator};
 
 #[cfg(not(feature="no-stdlib"))]
-use alloc_no_stdlib::{HeapPrealloc, HeapAlloc};
+use alloc_no_stdlib::{HeapPrealloc, HeapAlloc, HeapAllocZeroed};
 
 #[cfg(all(not(feature="no-stdlib"),feature="unsafe"))]
 use alloc_no_stdlib::{HeapAllocUninitialized};
 
 declare_stack_allocator_struct!(CallocAllocatedFreelist4096, 4096, calloc);
 declare_stack_allocator_struct!(MallocAllocatedFreelist4096, 4096, malloc);
+declare_stack_allocator_struct!(LockedAllocatedFreelist64, 64, mlock);
 declare_stack_allocator_struct!(StackAllocatedFreelist4, 4, stack);
 declare_stack_allocator_struct!(StackAllocatedFreelist8, 8, stack);
+declare_stack_allocator_struct!(StackAllocatedFreelist6, 6, stack);
+
+mod exported_allocators {
+  declare_stack_allocator_struct!(
+    /// A free list type other modules may name
+    #[derive(Debug)]
+    pub StackAllocatedFreelist16, 16, stack);
+  declare_stack_allocator_struct!(#[derive(Debug)] pub(crate) CallocAllocatedFreelist32, 32, calloc);
+  declare_stack_allocator_struct!(#[derive(Debug)] pub MallocAllocatedFreelist2, 2, malloc);
+  declare_stack_allocator_struct!(#[derive(Debug)] pub(crate) GlobalAllocatedFreelist32, 32, global);
+  declare_allocator_set!(
+    /// An allocator set other modules may name
+    pub ExportedAllocatorSet, StackAllocatedFreelist16, shared, {u8, u32});
+  declare_allocator_set!(pub(crate) ExportedSplitAllocatorSet, StackAllocatedFreelist16, {
+    u8_alloc : u8,
+    u32_alloc : u32,
+  });
+}
 declare_stack_allocator_struct!(GlobalAllocatedFreelist, 16, global);
 //trace_macros!(true);
 
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+struct HuffmanCode {
+  bits : u8,
+  value : u16,
+}
+declare_allocator_set!(BrotliAllocatorSet, StackAllocatedFreelist8, {
+  u8_alloc : u8,
+  u16_alloc : u16,
+  u32_alloc : u32,
+  huffman_alloc : HuffmanCode,
+});
+declare_allocator_set!(SharedAllocatorSet, StackAllocatedFreelist8, shared, {u8, u16, u32, [u16; 3]});
+
 define_allocator_memory_pool!(16, u8, [0; 1024 * 1024 * 100], global, global_buffer);
 define_allocator_memory_pool!(16, u8, [0; 1024 * 1024 * 100], global, global_buffer2);
+// a free list length that is not a power of two
+define_allocator_memory_pool!(100, u8, [0; 65536], global, global_buffer3);
+
+struct HandWrittenFreelist3<'a, T : 'a> {
+  freelist : [&'a mut [T]; 3],
+}
+define_stack_allocator_traits!(HandWrittenFreelist3, 3, stack);
+struct HandWrittenFreelist10000<'a, T : 'a> {
+  freelist : [&'a mut [T]; 10000],
+}
+define_stack_allocator_traits!(HandWrittenFreelist10000, 10000, calloc);
 extern {
   fn calloc(n_elem : usize, el_size : usize) -> *mut u8;
   fn malloc(len : usize) -> *mut u8;
@@ -202,9 +261,7 @@ println!("{:?}", ags.free_list_start);
 fn uninitialized_global_pool_test() {
   {
   let mut ags = GlobalAllocatedFreelist::<u8>::new_allocator(uninitialized);
-  unsafe {
-      bind_global_buffers_to_allocator!(ags, global_buffer, u8);
-  }
+  assert_eq!(bind_global_buffers_to_allocator!(ags, global_buffer, u8), Some(()));
   {
     let mut x = ags.alloc_cell(9999);
     x.slice_mut()[0] = 4;
@@ -500,9 +557,621 @@ fn malloc_pool_test() {
 fn global_pool_test() {
   {
   let mut ags = GlobalAllocatedFreelist::<u8>::new_allocator(bzero);
-  unsafe {
-  bind_global_buffers_to_allocator!(ags, global_buffer2, u8);
+  assert_eq!(bind_global_buffers_to_allocator!(ags, global_buffer2, u8), Some(()));
+  {
+    let mut x = ags.alloc_cell(9999);
+    x.slice_mut()[0] = 4;
+    let mut y = ags.alloc_cell(4);
+    y[0] = 5;
+    ags.free_cell(y);
+
+    let mut three = ags.alloc_cell(3);
+    three[0] = 6;
+    ags.free_cell(three);
+
+    let mut z = ags.alloc_cell(4);
+    z.slice_mut()[1] = 8;
+    let mut reget_three = ags.alloc_cell(4);
+    reget_three.slice_mut()[1] = 9;
+    //y.mem[0] = 6; // <-- this is an error (use after free)
+    assert_eq!(x[0], 4);
+    assert_eq!(z[0], 0);
+    assert_eq!(z[1], 8);
+    assert_eq!(reget_three[0], 0);
+    assert_eq!(reget_three[1], 9);
+    let mut _z = ags.alloc_cell(1);
+  }
+  }
+}
+
+#[test]
+fn ring_buffer_test() {
+  let mut stack_global_buffer = define_allocator_memory_pool!(4, u8, [0; 65536], stack);
+  let mut ags = StackAllocatedFreelist4::<u8>::new_allocator(&mut stack_global_buffer, bzero);
+  let mut ring = AllocRingBuffer::new(&mut ags, 4);
+  assert_eq!(ring.capacity(), 4);
+  assert_eq!(ring.pop_front(), None);
+  for i in 1..5 {
+    assert_eq!(ring.push_back(i), Ok(()));
+  }
+  assert_eq!(ring.push_back(5), Err(5));
+  assert_eq!(ring.pop_front(), Some(1));
+  assert_eq!(ring.pop_front(), Some(2));
+  assert_eq!(ring.push_back(5), Ok(()));
+  assert_eq!(ring.push_back(6), Ok(()));
+  {
+    let (first, second) = ring.as_slices();
+    assert_eq!(first, &[3, 4]);
+    assert_eq!(second, &[5, 6]);
+  }
+  ring.consume(3);
+  assert_eq!(ring.len(), 1);
+  assert_eq!(ring.head(), 1);
+  assert_eq!(ring.data().slice(), &[5, 6, 3, 4]);
+  {
+    let region = ring.contiguous_write_region();
+    assert_eq!(region.len(), 2);
+    region[0] = 7;
+    region[1] = 8;
+  }
+  ring.commit_write(2);
+  assert_eq!(ring.contiguous_write_region().len(), 1);
+  ring.resize(&mut ags, 8);
+  assert_eq!(ring.capacity(), 8);
+  {
+    let (first, second) = ring.as_slices();
+    assert_eq!(first, &[6, 7, 8]);
+    assert_eq!(second.len(), 0);
   }
+  assert_eq!(ring.contiguous_write_region().len(), 5);
+  assert_eq!(ring.pop_front(), Some(6));
+  ring.free(&mut ags);
+}
+
+fn use_allocator_set<Alloc : AllocatorSet<u16> + AllocatorSet<HuffmanCode> + Allocator<u32>>(alloc : &mut Alloc) {
+  let mut table = AllocatorSet::<HuffmanCode>::get_allocator(alloc).alloc_cell(16);
+  table.slice_mut()[3] = HuffmanCode{bits : 3, value : 7};
+  let mut counts = AllocatorSet::<u16>::get_allocator(alloc).alloc_cell(100);
+  counts.slice_mut()[99] = 65535;
+  let mut hist = <Alloc as Allocator<u32>>::alloc_cell(alloc, 256);
+  hist.slice_mut()[255] = 0xdeadbeef;
+  assert_eq!(table.slice()[3], HuffmanCode{bits : 3, value : 7});
+  assert_eq!(table.slice()[4], HuffmanCode::default());
+  assert_eq!(counts.slice()[99], 65535);
+  assert_eq!(hist.slice()[255], 0xdeadbeef);
+  <Alloc as Allocator<u32>>::free_cell(alloc, hist);
+  AllocatorSet::<u16>::get_allocator(alloc).free_cell(counts);
+  AllocatorSet::<HuffmanCode>::get_allocator(alloc).free_cell(table);
+}
+
+#[test]
+fn allocator_set_test() {
+  let mut byte_pool = define_allocator_memory_pool!(8, u8, [0; 65536], stack);
+  let mut set = unsafe {BrotliAllocatorSet::new_allocator_set(&mut byte_pool,
+                                                              (16384, bzero),
+                                                              (4096, bzero),
+                                                              (2048, bzero),
+                                                              (1024, bzero))};
+  use_allocator_set(&mut set);
+  {
+    let mut x = <BrotliAllocatorSet as Allocator<u8>>::alloc_cell(&mut set, 9999);
+    x[0] = 4;
+    let mut y = set.u16_alloc.alloc_cell(4);
+    y[0] = 5;
+    assert_eq!(set.u16_alloc.alloc_cell(3000).slice().len(), 3000);
+    assert_eq!(x[0], 4);
+    assert_eq!(y[0], 5);
+    set.u16_alloc.free_cell(y);
+  }
+  use_allocator_set(&mut set);
+}
+
+#[test]
+#[should_panic(expected = "OOM")]
+fn allocator_set_pool_too_small_test() {
+  let mut byte_pool = define_allocator_memory_pool!(8, u8, [0; 4096], stack);
+  let _set = unsafe {BrotliAllocatorSet::new_allocator_set(&mut byte_pool,
+                                                           (1024, bzero),
+                                                           (1024, bzero),
+                                                           (1024, bzero),
+                                                           (1024, bzero))};
+}
+
+#[test]
+fn typed_stack_allocator_test() {
+  let mut byte_pool = define_allocator_memory_pool!(8, u8, [0; 65536], stack);
+  let bytes = StackAllocatedFreelist8::<u8>::new_allocator(&mut byte_pool, bzero);
+  let mut ags = TypedStackAllocator::<u32, _>::new(bytes);
+  {
+    let mut x = ags.alloc_cell(9999);
+    x.slice_mut()[0] = 4;
+    let mut y = ags.alloc_cell(4);
+    y[0] = 5;
+    ags.free_cell(y);
+
+    let mut three = ags.alloc_cell(3);
+    three[0] = 6;
+    ags.free_cell(three);
+
+    let mut z = ags.alloc_cell(4);
+    z.slice_mut()[1] = 8;
+    let mut reget_three = ags.alloc_cell(4);
+    reget_three.slice_mut()[1] = 9;
+    assert_eq!(x[0], 4);
+    assert_eq!(z[0], 0);
+    assert_eq!(z[1], 8);
+    assert_eq!(reget_three[0], 0);
+    assert_eq!(reget_three[1], 9);
+    assert_eq!(x.mem.as_ptr() as usize % core::mem::align_of::<u32>(), 0);
+    assert_eq!(z.mem.as_ptr() as usize % core::mem::align_of::<u32>(), 0);
+    ags.free_cell(x);
+  }
+  let mut ags = ags.retype::<u8>();
+  let mut odd = ags.alloc_cell(3);
+  odd[2] = 1;
+  let mut ags = ags.retype::<u64>();
+  for _i in 0..64 {
+    let mut w = ags.alloc_cell(100);
+    assert_eq!(w.mem.as_ptr() as usize % core::mem::align_of::<u64>(), 0);
+    w[99] = 7;
+    ags.free_cell(w);
+  }
+  let mut ags = ags.retype::<u8>();
+  ags.free_cell(odd);
+}
+
+#[test]
+fn typed_cell_returns_all_bytes_test() {
+  let mut byte_pool = define_allocator_memory_pool!(8, u8, [0; 65536], stack);
+  // a pool that starts off alignment, so cells need padding
+  let bytes = StackAllocatedFreelist8::<u8>::new_allocator(&mut byte_pool[1..], bzero);
+  let mut ags = TypedStackAllocator::<u32, _>::new(bytes);
+  for _i in 0..100 {
+    let one = ags.alloc_cell(1);
+    assert_eq!(one.slice().len(), 1);
+    let x = ags.alloc_cell(3);
+    assert_eq!(x.slice().len(), 3);
+    assert_eq!(x.mem.as_ptr() as usize % core::mem::align_of::<u32>(), 0);
+    ags.free_cell(x);
+    ags.free_cell(one);
+  }
+  let free_bytes : usize = ags.bytes.system_resources.slice().iter().map(|region| region.len()).sum();
+  assert_eq!(free_bytes, 65535);
+  // a foreign cell gives back only the bytes it covers
+  let mut foreign = [7u32; 1];
+  let foreign_ptr = foreign.as_mut_ptr() as *mut u8;
+  ags.free_cell(AllocatedStackMemory::<u32>{mem : &mut foreign});
+  assert!(ags.bytes.system_resources.slice().iter().any(|region| region.as_ptr() == foreign_ptr && region.len() == 4));
+}
+
+#[test]
+fn shared_allocator_set_test() {
+  let mut byte_pool = define_allocator_memory_pool!(8, u8, [0; 65536], stack);
+  let mut set = SharedAllocatorSet::new_allocator_set(&mut byte_pool, bzero);
+  for _i in 0..16 {
+    let mut codes = <SharedAllocatorSet as Allocator<[u16; 3]>>::alloc_cell(&mut set, 1000);
+    codes[999] = [1, 2, 3];
+    let mut bits = <SharedAllocatorSet as Allocator<u8>>::alloc_cell(&mut set, 1001);
+    bits[1000] = 255;
+    assert_eq!(bits.slice().len(), 1001);
+    let mut counts = <SharedAllocatorSet as Allocator<u32>>::alloc_cell(&mut set, 4000);
+    counts[3999] = 0xdeadbeef;
+    assert_eq!(codes[999], [1, 2, 3]);
+    assert_eq!(codes[0], [0, 0, 0]);
+    assert_eq!(bits[1000], 255);
+    assert_eq!(counts[3999], 0xdeadbeef);
+    assert_eq!(counts.mem.as_ptr() as usize % core::mem::align_of::<u32>(), 0);
+    <SharedAllocatorSet as Allocator<u32>>::free_cell(&mut set, counts);
+    <SharedAllocatorSet as Allocator<u8>>::free_cell(&mut set, bits);
+    <SharedAllocatorSet as Allocator<[u16; 3]>>::free_cell(&mut set, codes);
+  }
+  // space not claimed by the loop above is available to any of the types; the odd-length u8
+  // cell leaves a misaligned u32 run behind, which stays free for other cells
+  let big = <SharedAllocatorSet as Allocator<u16>>::alloc_cell(&mut set, 13000);
+  assert_eq!(big.slice().len(), 13000);
+  let odd = <SharedAllocatorSet as Allocator<u8>>::alloc_cell(&mut set, 16000);
+  assert_eq!(odd.slice().len(), 16000);
+}
+
+#[test]
+fn static_array_test() {
+  let lengths : [usize; 3] = static_array!(7; 3);
+  assert_eq!(lengths, [7, 7, 7]);
+  let mut buffer = [0u8; 16];
+  let mut small = HandWrittenFreelist3::<u8>::default();
+  assert_eq!(small.slice().len(), 3);
+  small.slice_mut()[2] = &mut buffer;
+  assert_eq!(small[2].len(), 16);
+  let large = HandWrittenFreelist10000::<u8>::default();
+  assert_eq!(large.slice().len(), 10000);
+  assert!(large.slice().iter().all(|free| free.len() == 0));
+}
+
+#[test]
+fn const_generic_freelist_test() {
+  let mut stack_global_buffer = define_allocator_memory_pool!(6, u8, [0; 65536], stack);
+  let mut ags = StackAllocatedFreelist6::<u8>::new_allocator(&mut stack_global_buffer, bzero);
+  assert_eq!(ags.free_list_start, 5);
+  assert_eq!(ags.system_resources.slice().len(), 6);
+  {
+    let mut x = ags.alloc_cell(9999);
+    x.slice_mut()[0] = 4;
+    let mut y = ags.alloc_cell(4);
+    y[0] = 5;
+    ags.free_cell(y);
+
+    let mut three = ags.alloc_cell(3);
+    three[0] = 6;
+    ags.free_cell(three);
+
+    let mut z = ags.alloc_cell(4);
+    z.slice_mut()[1] = 8;
+    let mut reget_three = ags.alloc_cell(4);
+    reget_three.slice_mut()[1] = 9;
+    assert_eq!(x[0], 4);
+    assert_eq!(z[0], 0);
+    assert_eq!(z[1], 8);
+    assert_eq!(reget_three[0], 0);
+    assert_eq!(reget_three[1], 9);
+  }
+  let mut other_buffer = define_allocator_memory_pool!(100, u16, [0; 4096], stack);
+  let mut other = StackFreelist::<u16, 100>::new_allocator(&mut other_buffer, uninitialized);
+  let mut cells = [AllocatedStackMemory::<u16>::default(), AllocatedStackMemory::<u16>::default(),
+                   AllocatedStackMemory::<u16>::default()];
+  for (i, cell) in cells.iter_mut().enumerate() {
+    *cell = other.alloc_cell(10 * (i + 1));
+    cell[i] = i as u16 + 1;
+  }
+  for cell in cells.iter_mut() {
+    other.free_cell(core::mem::replace(cell, AllocatedStackMemory::<u16>::default()));
+  }
+  assert_eq!(other.free_list_start, 96);
+}
+
+#[test]
+fn exported_freelist_test() {
+  let mut stack_global_buffer = define_allocator_memory_pool!(16, u8, [0; 256], stack);
+  let mut ags = exported_allocators::StackAllocatedFreelist16::<u8>::new_allocator(&mut stack_global_buffer, bzero);
+  let mut x = ags.alloc_cell(3);
+  x[2] = 7;
+  assert!(format!("{:?}", ags.system_resources).starts_with("StackFreelist { freelist: [[], "));
+  ags.free_cell(x);
+  let mut calloc_global_buffer = unsafe{define_allocator_memory_pool!(32, u8, [0; 1024], calloc)};
+  let mut calloc_ags = exported_allocators::CallocAllocatedFreelist32::<u8>::new_allocator(&mut calloc_global_buffer.data, bzero);
+  assert_eq!(calloc_ags.alloc_cell(1000).slice().len(), 1000);
+  let mut malloc_global_buffer = unsafe{define_allocator_memory_pool!(2, u8, [0; 1024], malloc)};
+  let mut malloc_ags = exported_allocators::MallocAllocatedFreelist2::<u8>::new_allocator(&mut malloc_global_buffer.data, bzero);
+  assert_eq!(malloc_ags.alloc_cell(1000).slice().len(), 1000);
+  let global_ags = exported_allocators::GlobalAllocatedFreelist32::<u8>::new_allocator(bzero);
+  assert_eq!(format!("{:?}", global_ags.system_resources), "GlobalAllocatedFreelist32 { freelist: [] }");
+  let mut set_pool = define_allocator_memory_pool!(16, u8, [0; 1024], stack);
+  let mut set = exported_allocators::ExportedAllocatorSet::new_allocator_set(&mut set_pool, bzero);
+  let counts = <exported_allocators::ExportedAllocatorSet as Allocator<u32>>::alloc_cell(&mut set, 100);
+  <exported_allocators::ExportedAllocatorSet as Allocator<u32>>::free_cell(&mut set, counts);
+  let mut split_pool = define_allocator_memory_pool!(16, u8, [0; 1024], stack);
+  let mut split = unsafe {exported_allocators::ExportedSplitAllocatorSet::new_allocator_set(&mut split_pool, (16, bzero), (16, bzero))};
+  assert_eq!(AllocatorSet::<u32>::get_allocator(&mut split).alloc_cell(16).slice().len(), 16);
+}
+
+#[test]
+#[cfg(not(feature="no-stdlib"))]
+fn global_pool_bind_once_test() {
+  let handles : Vec<_> = (0..4).map(|i| std::thread::spawn(move || {
+    let mut ags = GlobalAllocatedFreelist::<u8>::new_allocator(bzero);
+    match bind_global_buffers_to_allocator!(ags, global_buffer3, u8) {
+      Some(()) => {
+        assert_eq!(ags.system_resources.freelist.len(), 100);
+        let mut x = ags.alloc_cell(60000);
+        x[59999] = i;
+        assert_eq!(x[59999], i);
+        true
+      },
+      None => {
+        assert_eq!(ags.free_list_start, 0);
+        false
+      },
+    }
+  })).collect();
+  let bound = handles.into_iter().map(|h| h.join().unwrap()).filter(|b| *b).count();
+  assert_eq!(bound, 1);
+  assert!(global_buffer3.is_claimed());
+  let mut late = GlobalAllocatedFreelist::<u8>::new_allocator(bzero);
+  assert_eq!(bind_global_buffers_to_allocator!(late, global_buffer3, u8), None);
+}
+
+#[test]
+fn sync_stack_allocator_test() {
+  let mut stack_global_buffer = define_allocator_memory_pool!(64, u32, [0; 65536], stack);
+  let shared = SyncStackAllocator::new(StackFreelist::<u32, 64>::new_allocator(&mut stack_global_buffer, bzero));
+  std::thread::scope(|scope| {
+    for t in 0..4u32 {
+      let mut ags = shared.handle();
+      scope.spawn(move || {
+        for i in 0..1000u32 {
+          let mut x = ags.alloc_cell(100 + t as usize);
+          assert_eq!(x[0], 0);
+          x[0] = t;
+          x[99] = i;
+          let mut y = ags.alloc_cell(4);
+          y[3] = t + i;
+          assert_eq!(x[0], t);
+          assert_eq!(x[99], i);
+          assert_eq!(y[3], t + i);
+          ags.free_cell(y);
+          ags.free_cell(x);
+        }
+      });
+    }
+  });
+  let cell = {
+    let mut ags = shared.handle();
+    ags.alloc_cell(4)
+  };
+  std::thread::scope(|scope| {
+    let mut ags = shared.handle();
+    scope.spawn(move || ags.free_cell(cell));
+  });
+  let ags = shared.into_inner();
+  assert_eq!(ags.system_resources.slice().len(), 64);
+}
+
+#[test]
+fn thread_cache_test() {
+  let mut stack_global_buffer = define_allocator_memory_pool!(64, u32, [0; 65536], stack);
+  let shared = SyncStackAllocator::new(StackFreelist::<u32, 64>::new_allocator(&mut stack_global_buffer, bzero));
+  {
+    let mut cache = ThreadCache::<u32, _, 2>::new(shared.handle(), bzero);
+    let mut x = cache.alloc_cell(100);
+    x[0] = 4;
+    let x_ptr = x.mem.as_ptr();
+    cache.free_cell(x);
+    assert_eq!(cache.cached_cells(), 1);
+    let free_list_start = shared.lock().free_list_start;
+    // same class, no larger than the cached cell: served from the cache without the lock
+    let y = cache.alloc_cell(90);
+    assert_eq!(y.mem.as_ptr(), x_ptr);
+    assert_eq!(y.slice().len(), 100);
+    assert_eq!(y[0], 0);
+    assert_eq!(shared.lock().free_list_start, free_list_start);
+    cache.free_cell(y);
+    // a request bigger than the cached cell misses
+    let z = cache.alloc_cell(120);
+    assert!(z.mem.as_ptr() != x_ptr);
+    // a request just below a power of two may reuse a cell from the next class
+    let w = cache.alloc_cell(63);
+    assert_eq!(w.mem.as_ptr(), x_ptr);
+    let a = cache.alloc_cell(8);
+    let b = cache.alloc_cell(9);
+    let c = cache.alloc_cell(10);
+    cache.free_cell(a);
+    cache.free_cell(b);
+    // the class holding 8..15 is full, so this one goes to the shared pool
+    let c_free_list_start = shared.lock().free_list_start;
+    cache.free_cell(c);
+    assert_eq!(shared.lock().free_list_start, c_free_list_start - 1);
+    cache.free_cell(z);
+    cache.free_cell(w);
+    assert_eq!(cache.cached_cells(), 4);
+    cache.flush();
+    assert_eq!(cache.cached_cells(), 0);
+  }
+  {
+    let mut cache = ThreadCache::<u32, _, 2>::new(shared.handle(), bzero);
+    let x = cache.alloc_cell(100);
+    cache.free_cell(x);
+    let free_list_start = shared.lock().free_list_start;
+    // dropping the cache hands its cells back to the shared pool
+    core::mem::drop(cache);
+    assert_eq!(shared.lock().free_list_start, free_list_start - 1);
+  }
+  std::thread::scope(|scope| {
+    for t in 0..4u32 {
+      let handle = shared.handle();
+      scope.spawn(move || {
+        let mut cache = ThreadCache::<u32, _, 4>::new(handle, bzero);
+        for i in 0..1000u32 {
+          let mut x = cache.alloc_cell(100 + t as usize);
+          assert_eq!(x[99], 0);
+          x[99] = i;
+          let mut y = cache.alloc_cell(4);
+          y[3] = t + i;
+          assert_eq!(x[99], i);
+          cache.free_cell(y);
+          cache.free_cell(x);
+        }
+        if t % 2 == 0 {
+          cache.into_inner();
+        }
+      });
+    }
+  });
+}
+
+#[test]
+fn slab_allocator_test() {
+  let mut stack_global_buffer = define_allocator_memory_pool!(64, u8, [0; 4096], stack);
+  let slabs = StackFreelist::<u8, 64>::default();
+  let mut ags = SlabAllocator::<u8, _, 3>::new(&mut stack_global_buffer, slabs, [(16, 32), (64, 16), (256, 4)], bzero);
+  {
+    let mut x = ags.alloc_cell(15);
+    assert_eq!(x.slice().len(), 16);
+    x[0] = 4;
+    let x_ptr = x.mem.as_ptr();
+    let mut y = ags.alloc_cell(17);
+    assert_eq!(y.slice().len(), 64);
+    y[0] = 5;
+    ags.free_cell(x);
+    // the freed cell is the first one handed back out, zeroed by the initializer
+    let z = ags.alloc_cell(3);
+    assert_eq!(z.mem.as_ptr(), x_ptr);
+    assert_eq!(z[0], 0);
+    let mut big = [ags.alloc_cell(200), ags.alloc_cell(256), ags.alloc_cell(129), ags.alloc_cell(250)];
+    big[3][0] = 6;
+    assert_eq!(ags.class_free[2], 0);
+    // the largest class is exhausted, but smaller requests are still served from their own class
+    let w = ags.alloc_cell(64);
+    assert_eq!(w.slice().len(), 64);
+    ags.free_cell(y);
+    ags.free_cell(z);
+    ags.free_cell(w);
+    for cell in big.iter_mut() {
+      ags.free_cell(core::mem::replace(cell, AllocatedStackMemory::default()));
+    }
+    assert_eq!(ags.class_free, [32, 16, 4]);
+  }
+}
+
+#[test]
+#[should_panic(expected = "OOM")]
+fn slab_allocator_exhausted_test() {
+  let mut stack_global_buffer = define_allocator_memory_pool!(4, u8, [0; 256], stack);
+  let mut ags = SlabAllocator::<u8, _, 2>::new(&mut stack_global_buffer, StackFreelist::<u8, 4>::default(), [(16, 2), (64, 2)], bzero);
+  let _x = ags.alloc_cell(20);
+  let _y = ags.alloc_cell(20);
+  let _z = ags.alloc_cell(20);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "cell was not allocated from this SlabAllocator")]
+fn slab_allocator_foreign_cell_test() {
+  let mut stack_global_buffer = define_allocator_memory_pool!(4, u8, [0; 256], stack);
+  let mut other_buffer = define_allocator_memory_pool!(4, u8, [0; 64], stack);
+  // the last class only owns its 2 cells, not the 4 spare slots behind them
+  let mut ags = SlabAllocator::<u8, _, 2>::new(&mut stack_global_buffer, StackFreelist::<u8, 8>::default(), [(16, 2), (64, 2)], bzero);
+  ags.free_cell(AllocatedStackMemory::<u8>{mem : &mut other_buffer[..]});
+}
+
+#[test]
+fn buddy_allocator_test() {
+  let mut stack_global_buffer = define_allocator_memory_pool!(4, u32, [0; 1000], stack);
+  // 1000 items hold 62 whole blocks of 16: the tree covers 64 leaves, the last two never free
+  let mut tree = [0u8; 127];
+  assert_eq!(BuddyAllocator::<u32>::tree_len(1000, 16), tree.len());
+  let mut ags = BuddyAllocator::new(&mut stack_global_buffer, 16, &mut tree, bzero);
+  assert_eq!(ags.largest_free_block(), 512);
+  {
+    let mut x = ags.alloc_cell(16);
+    x[0] = 4;
+    let x_ptr = x.mem.as_ptr();
+    let mut y = ags.alloc_cell(17);
+    assert_eq!(y.slice().len(), 32);
+    y[0] = 5;
+    // the upper half of the tree is missing its last two leaves, so it never merges to 512
+    assert_eq!(ags.largest_free_block(), 256);
+    let mut z = ags.alloc_cell(250);
+    z[249] = 6;
+    assert_eq!(ags.largest_free_block(), 256);
+    ags.free_cell(x);
+    let w = ags.alloc_cell(16);
+    assert_eq!(w.mem.as_ptr(), x_ptr);
+    assert_eq!(w[0], 0);
+    ags.free_cell(y);
+    ags.free_cell(w);
+    ags.free_cell(z);
+    // every block merged back with its buddy
+    assert_eq!(ags.largest_free_block(), 512);
+    let mut cells = [ags.alloc_cell(256), ags.alloc_cell(256), ags.alloc_cell(256)];
+    assert_eq!(ags.largest_free_block(), 128);
+    for cell in cells.iter_mut() {
+      cell[255] = 7;
+      ags.free_cell(core::mem::replace(cell, AllocatedStackMemory::default()));
+    }
+    assert_eq!(ags.largest_free_block(), 512);
+  }
+}
+
+#[test]
+#[should_panic(expected = "OOM")]
+fn buddy_allocator_oom_test() {
+  let mut stack_global_buffer = define_allocator_memory_pool!(4, u32, [0; 1000], stack);
+  let mut tree = [0u8; 127];
+  let mut ags = BuddyAllocator::new(&mut stack_global_buffer, 16, &mut tree, bzero);
+  let _x = ags.alloc_cell(512);
+  let _y = ags.alloc_cell(257);
+}
+
+#[test]
+#[should_panic(expected = "cell was not allocated from this BuddyAllocator")]
+fn buddy_allocator_split_cell_test() {
+  let mut stack_global_buffer = define_allocator_memory_pool!(4, u32, [0; 1000], stack);
+  let mut tree = [0u8; 127];
+  let mut ags = BuddyAllocator::new(&mut stack_global_buffer, 16, &mut tree, bzero);
+  let x = ags.alloc_cell(16);
+  // freeing the low half must not free the block while the high half is live
+  let (low, high) = x.mem.split_at_mut(8);
+  ags.free_cell(AllocatedStackMemory::<u32>{mem : low});
+  high[0] = 1;
+}
+
+#[test]
+fn bump_allocator_test() {
+  let mut stack_global_buffer = define_allocator_memory_pool!(4, u8, [0; 1024], stack);
+  let mut ags = BumpAllocator::from(&mut stack_global_buffer);
+  {
+    let mut x = ags.alloc_cell(100);
+    x[0] = 4;
+    let mut y = ags.alloc_cell(4);
+    y[0] = 5;
+    let y_ptr = y.mem.as_ptr();
+    // only the most recent cell can be given back
+    ags.free_cell(x);
+    assert_eq!(ags.used(), 104);
+    ags.free_cell(y);
+    assert_eq!(ags.used(), 100);
+    let mut three = ags.alloc_cell(3);
+    assert_eq!(three.mem.as_ptr(), y_ptr);
+    assert_eq!(three[0], 0);
+    three[0] = 6;
+    let used = ags.scope(|child| {
+      let mut z = child.alloc_cell(900);
+      z[899] = 7;
+      return child.used();
+    });
+    assert_eq!(used, 900);
+    assert_eq!(ags.remaining(), 1024 - 103);
+    assert_eq!(three[0], 6);
+  }
+  unsafe { ags.reset() };
+  assert_eq!(ags.remaining(), 1024);
+  let z = ags.alloc_cell(1024);
+  assert_eq!(z[0], 0);
+}
+
+#[test]
+fn bump_allocator_from_pool_test() {
+  {
+    let mut calloc_global_buffer = unsafe {define_allocator_memory_pool!(4096, u8, [0; 1024], calloc)};
+    let mut ags = BumpAllocator::from(&mut calloc_global_buffer);
+    assert_eq!(ags.capacity(), 1024);
+    let x = ags.alloc_cell(1024);
+    assert_eq!(x[1023], 0);
+  }
+  #[cfg(not(feature="no-stdlib"))]
+  {
+    let mut heap_global_buffer = define_allocator_memory_pool!(4096, u16, [0; 1024], heap);
+    let mut ags = BumpAllocator::from(&mut heap_global_buffer);
+    let mut x = ags.alloc_cell(1000);
+    x[999] = 3;
+    assert_eq!(ags.remaining(), 24);
+  }
+}
+
+#[test]
+#[should_panic(expected = "OOM")]
+fn bump_allocator_oom_test() {
+  let mut stack_global_buffer = define_allocator_memory_pool!(4, u8, [0; 64], stack);
+  let mut ags = BumpAllocator::from(&mut stack_global_buffer);
+  let _x = ags.alloc_cell(60);
+  let _y = ags.alloc_cell(5);
+}
+
+#[test]
+fn tlsf_pool_test() {
+  {
+  let mut stack_global_buffer = define_allocator_memory_pool!(4, u8, [0; 65536], stack);
+  let mut tags = [TlsfTag::default(); 65536 / 16];
+  let mut ags = TlsfAllocator::new(&mut stack_global_buffer, 16, &mut tags, bzero);
   {
     let mut x = ags.alloc_cell(9999);
     x.slice_mut()[0] = 4;
@@ -529,4 +1198,689 @@ fn global_pool_test() {
   }
 }
 
+#[test]
+fn tlsf_coalesce_test() {
+  let mut stack_global_buffer = define_allocator_memory_pool!(4, u32, [0; 4096], stack);
+  let mut tags = [TlsfTag::default(); 4096 / 8];
+  let mut ags = TlsfAllocator::new(&mut stack_global_buffer, 8, &mut tags, bzero);
+  assert_eq!(ags.largest_free_block(), 4096);
+  {
+    let mut cells = [ags.alloc_cell(1000), ags.alloc_cell(1000), ags.alloc_cell(1000), ags.alloc_cell(1000)];
+    assert_eq!(ags.largest_free_block(), 96);
+    for cell in cells.iter_mut() {
+      cell[999] = 3;
+    }
+    // free out of order: each cell merges with whichever neighbours are already free
+    for index in [1, 3, 0, 2].iter() {
+      ags.free_cell(core::mem::replace(&mut cells[*index], AllocatedStackMemory::default()));
+    }
+    assert_eq!(ags.largest_free_block(), 4096);
+    let all = ags.alloc_cell(4096);
+    assert_eq!(all[999], 0);
+    ags.free_cell(all);
+    // interleaved sizes still leave the pool whole once everything is back
+    let a = ags.alloc_cell(7);
+    let b = ags.alloc_cell(300);
+    let c = ags.alloc_cell(9);
+    ags.free_cell(b);
+    let d = ags.alloc_cell(250);
+    ags.free_cell(a);
+    ags.free_cell(c);
+    ags.free_cell(d);
+    assert_eq!(ags.largest_free_block(), 4096);
+  }
+}
+
+#[test]
+#[should_panic(expected = "OOM")]
+fn tlsf_oom_test() {
+  let mut stack_global_buffer = define_allocator_memory_pool!(4, u8, [0; 256], stack);
+  let mut tags = [TlsfTag::default(); 256 / 16];
+  let mut ags = TlsfAllocator::new(&mut stack_global_buffer, 16, &mut tags, bzero);
+  let _x = ags.alloc_cell(200);
+  let _y = ags.alloc_cell(57);
+}
+
+#[test]
+#[should_panic(expected = "cell was not allocated from this TlsfAllocator")]
+fn tlsf_split_cell_test() {
+  let mut stack_global_buffer = define_allocator_memory_pool!(4, u8, [0; 256], stack);
+  let mut tags = [TlsfTag::default(); 256 / 16];
+  let mut ags = TlsfAllocator::new(&mut stack_global_buffer, 16, &mut tags, bzero);
+  let x = ags.alloc_cell(20);
+  // the low half still rounds up to both granules of the block, the high half is live
+  let (low, high) = x.mem.split_at_mut(17);
+  ags.free_cell(AllocatedStackMemory::<u8>{mem : low});
+  high[0] = 1;
+}
+
+#[test]
+fn try_alloc_cell_test() {
+  let mut stack_global_buffer = define_allocator_memory_pool!(4, u8, [0; 256], stack);
+  let mut ags = StackAllocatedFreelist4::<u8>::new_allocator(&mut stack_global_buffer, bzero);
+  assert!(ags.try_alloc_cell(257).is_none());
+  let x = ags.try_alloc_cell(200).unwrap();
+  assert!(ags.try_alloc_cell(100).is_none());
+  ags.free_cell(x);
+  assert_eq!(ags.try_alloc_cell(100).unwrap().slice().len(), 100);
+}
+
+#[test]
+fn fallback_test() {
+  let mut small_buffer = define_allocator_memory_pool!(4, u8, [0; 256], stack);
+  let mut large_buffer = define_allocator_memory_pool!(4, u8, [0; 4096], stack);
+  let mut ags = Fallback::new(StackAllocatedFreelist4::<u8>::new_allocator(&mut small_buffer, bzero),
+                              StackAllocatedFreelist4::<u8>::new_allocator(&mut large_buffer, bzero));
+  {
+    let mut x = ags.alloc_cell(200);
+    x[0] = 4;
+    // the primary pool cannot fit a second cell of this size
+    let mut y = ags.alloc_cell(200);
+    y[0] = 5;
+    match x {
+      FallbackCell::Primary(_) => {},
+      FallbackCell::Secondary(_) => panic!("expected the primary pool"),
+    }
+    match y {
+      FallbackCell::Secondary(_) => {},
+      FallbackCell::Primary(_) => panic!("expected the secondary pool"),
+    }
+    assert!(ags.try_alloc_cell(5000).is_none());
+    ags.free_cell(y);
+    ags.free_cell(x);
+    // each cell went back to its own pool, so the primary has room again
+    assert_eq!(ags.secondary.free_list_start, 2);
+    let z = ags.alloc_cell(200);
+    match z {
+      FallbackCell::Primary(ref cell) => assert_eq!(cell[0], 0),
+      FallbackCell::Secondary(_) => panic!("expected the primary pool"),
+    }
+  }
+}
+
+#[cfg(not(feature="no-stdlib"))]
+#[test]
+fn fallback_heap_test() {
+  let mut small_buffer = define_allocator_memory_pool!(4, u32, [0; 256], stack);
+  let mut ags = Fallback::new(StackAllocatedFreelist4::<u32>::new_allocator(&mut small_buffer, bzero),
+                              HeapAlloc::<u32>::new(0));
+  let mut x = ags.alloc_cell(1000);
+  x[999] = 6;
+  assert_eq!(x.slice().len(), 1000);
+  let y = ags.alloc_cell(10);
+  match y {
+    FallbackCell::Primary(_) => {},
+    FallbackCell::Secondary(_) => panic!("expected the primary pool"),
+  }
+  ags.free_cell(x);
+  ags.free_cell(y);
+}
+
+#[test]
+fn fallback_typed_test() {
+  // every primary below is too small and must hand the request on instead of panicking
+  let mut spill_buffer = define_allocator_memory_pool!(4, u32, [0; 4096], stack);
+  let mut spill = StackAllocatedFreelist4::<u32>::new_allocator(&mut spill_buffer, bzero);
+  let mut typed_pool = define_allocator_memory_pool!(8, u8, [0; 1024], stack);
+  let typed = TypedStackAllocator::<u32, _>::new(StackAllocatedFreelist8::<u8>::new_allocator(&mut typed_pool, bzero));
+  let mut shared_pool = define_allocator_memory_pool!(8, u8, [0; 1024], stack);
+  let shared = SharedAllocatorSet::new_allocator_set(&mut shared_pool, bzero);
+  let mut split_pool = define_allocator_memory_pool!(8, u8, [0; 1024], stack);
+  let split = unsafe {BrotliAllocatorSet::new_allocator_set(&mut split_pool, (16, bzero), (16, bzero), (16, bzero), (16, bzero))};
+
+  let mut ags = Fallback::new(typed, spill);
+  let x = ags.alloc_cell(1000);
+  assert!(match x { FallbackCell::Secondary(_) => true, FallbackCell::Primary(_) => false });
+  ags.free_cell(x);
+  spill = ags.into_inner().1;
+
+  let mut ags = Fallback::new(shared, spill);
+  let x = Allocator::<u32>::alloc_cell(&mut ags, 1000);
+  assert!(match x { FallbackCell::Secondary(_) => true, FallbackCell::Primary(_) => false });
+  Allocator::<u32>::free_cell(&mut ags, x);
+  spill = ags.into_inner().1;
+
+  let mut ags = Fallback::new(split, spill);
+  let x = Allocator::<u32>::alloc_cell(&mut ags, 1000);
+  assert!(match x { FallbackCell::Secondary(_) => true, FallbackCell::Primary(_) => false });
+  Allocator::<u32>::free_cell(&mut ags, x);
+}
+
+#[test]
+fn segregate_test() {
+  let mut small_buffer = define_allocator_memory_pool!(16, u16, [0; 1024], stack);
+  let mut large_buffer = define_allocator_memory_pool!(4, u16, [0; 65536], stack);
+  let slab = SlabAllocator::<u16, _, 2>::new(&mut small_buffer, StackFreelist::<u16, 48>::default(), [(16, 32), (32, 16)], bzero);
+  let mut ags = Segregate::<33, _, _>::new(slab, StackAllocatedFreelist4::<u16>::new_allocator(&mut large_buffer, bzero));
+  {
+    let mut x = ags.alloc_cell(20);
+    x[0] = 4;
+    let mut y = ags.alloc_cell(33);
+    y[32] = 5;
+    match x {
+      SegregatedCell::Small(ref cell) => assert_eq!(cell.slice().len(), 32),
+      SegregatedCell::Large(_) => panic!("expected the small backend"),
+    }
+    match y {
+      SegregatedCell::Large(ref cell) => assert_eq!(cell.slice().len(), 33),
+      SegregatedCell::Small(_) => panic!("expected the large backend"),
+    }
+    ags.free_cell(x);
+    ags.free_cell(y);
+    assert_eq!(ags.small.class_free, [32, 16]);
+    assert_eq!(ags.large.free_list_start, 2);
+  }
+}
 
+#[cfg(not(feature="no-stdlib"))]
+#[test]
+fn segregate_heap_test() {
+  let mut small_buffer = define_allocator_memory_pool!(4, u8, [0; 1024], stack);
+  let mut ags = Segregate::<64, _, _>::new(StackAllocatedFreelist4::<u8>::new_allocator(&mut small_buffer, bzero),
+                                           HeapAlloc::<u8>::new(0));
+  let mut x = ags.alloc_cell(1 << 20);
+  x[(1 << 20) - 1] = 6;
+  let y = ags.try_alloc_cell(63).unwrap();
+  match y {
+    SegregatedCell::Small(_) => {},
+    SegregatedCell::Large(_) => panic!("expected the small backend"),
+  }
+  ags.free_cell(x);
+  ags.free_cell(y);
+}
+
+#[test]
+fn limited_test() {
+  let mut stack_global_buffer = define_allocator_memory_pool!(4, u8, [0; 65536], stack);
+  let mut ags = Limited::new(StackAllocatedFreelist4::<u8>::new_allocator(&mut stack_global_buffer, bzero), 1000);
+  {
+    let x = ags.alloc_cell(600);
+    let y = ags.alloc_cell(400);
+    assert_eq!(ags.live(), 1000);
+    assert!(ags.try_alloc_cell(1).is_none());
+    ags.free_cell(x);
+    assert_eq!(ags.remaining(), 600);
+    let z = ags.try_alloc_cell(600).unwrap();
+    assert!(ags.try_alloc_cell(1).is_none());
+    ags.free_cell(y);
+    ags.free_cell(z);
+    assert_eq!(ags.live(), 0);
+    // a cell the limit never counted leaves live at zero instead of wrapping
+    let mut foreign = [0u8; 16];
+    let w = ags.alloc_cell(8);
+    ags.free_cell(AllocatedStackMemory::<u8>{mem : &mut foreign});
+    assert_eq!(ags.live(), 0);
+    assert_eq!(ags.remaining(), 1000);
+    ags.free_cell(w);
+  }
+}
+
+#[cfg(not(feature="no-stdlib"))]
+#[test]
+#[should_panic(expected = "exceeds the limit of 4096 live items")]
+fn limited_heap_test() {
+  let mut ags = Limited::new(HeapAlloc::<u32>::new(0), 4096);
+  let _x = ags.alloc_cell(4000);
+  let _y = ags.alloc_cell(97);
+}
+
+#[cfg(all(not(feature="no-stdlib"),feature="unsafe"))]
+#[test]
+fn heap_alloc_uninitialized_zeroed_test() {
+  let mut halloc = unsafe{HeapAllocUninitialized::<[u32; 3]>::new()};
+  let mut x = halloc.alloc_cell(1 << 20);
+  assert_eq!(x[(1 << 20) - 1], [0, 0, 0]);
+  x[0][2] = 4;
+  halloc.free_cell(x);
+  let pool = unsafe{HeapPrealloc::<u64>::new_uninitialized_memory_pool(1 << 20)};
+  assert!(pool.iter().all(|item| *item == 0));
+  assert_eq!(halloc.alloc_cell(0).slice().len(), 0);
+  // too large to lay out, so try_alloc_cell fails and Fallback moves on instead of panicking
+  let mut ags = Fallback::new(halloc, HeapAlloc::<[u32; 3]>::new([0; 3]));
+  assert!(ags.try_alloc_cell(usize::MAX / 4).is_none());
+}
+
+#[cfg(not(feature="no-stdlib"))]
+#[test]
+fn heap_alloc_zeroed_test() {
+  let mut halloc = HeapAllocZeroed::<u32>::new();
+  let mut x = halloc.alloc_cell(1024 * 1024 * 100 / 4);
+  assert_eq!(x[1024 * 1024 * 100 / 4 - 1], 0);
+  x[0] = 4;
+  let y = halloc.try_alloc_cell(usize::MAX / 2).map(|cell| cell.slice().len());
+  assert_eq!(y, None);
+  halloc.free_cell(x);
+  let mut zst = HeapAllocZeroed::<[u8; 0]>::new();
+  assert_eq!(zst.alloc_cell(7).slice().len(), 7);
+}
+
+#[test]
+fn stack_pool_drop_test() {
+  let shared = std::rc::Rc::new(7u32);
+  {
+    let mut stack_global_buffer : [Option<std::rc::Rc<u32>>; 64] = core::array::from_fn(|_| None);
+    let mut ags = StackAllocatedFreelist4::<Option<std::rc::Rc<u32>>>::new_allocator(&mut stack_global_buffer, bzero);
+    let mut x = ags.alloc_cell(16);
+    for item in x.slice_mut().iter_mut() {
+      *item = Some(shared.clone());
+    }
+    assert_eq!(std::rc::Rc::strong_count(&shared), 17);
+    // free_cell leaves the values in place until bzero overwrites them on the next alloc_cell
+    ags.free_cell(x);
+    assert_eq!(std::rc::Rc::strong_count(&shared), 17);
+    let mut y = ags.alloc_cell(16);
+    assert_eq!(std::rc::Rc::strong_count(&shared), 1);
+    for item in y.slice_mut().iter_mut() {
+      *item = Some(shared.clone());
+    }
+    ags.free_cell_dropping(y);
+    assert_eq!(std::rc::Rc::strong_count(&shared), 1);
+    let mut z = ags.alloc_cell(4);
+    z[0] = Some(shared.clone());
+    ags.initialize = uninitialized;
+    ags.free_cell(z);
+    let _w = ags.alloc_cell(4);
+    assert_eq!(std::rc::Rc::strong_count(&shared), 2);
+  }
+  // values never overwritten are dropped along with the pool
+  assert_eq!(std::rc::Rc::strong_count(&shared), 1);
+}
+
+#[test]
+fn zero_policy_test() {
+  // the pool is deliberately not zero so the tests can tell which items were initialized
+  let mut stack_global_buffer = define_allocator_memory_pool!(4, u8, [7; 1024], stack);
+  {
+    let mut ags = StackAllocatedFreelist4::<u8>::new_allocator(&mut stack_global_buffer, bzero);
+    assert_eq!(ags.zero_policy, ZeroPolicy::DirtyWatermark);
+    let mut x = ags.alloc_cell(64);
+    // never handed out before, so left as the pool was given
+    assert_eq!(x[0], 7);
+    x[0] = 4;
+    x[63] = 4;
+    ags.free_cell(x);
+    // the freed cell was handed out before, the rest of the pool was not
+    let y = ags.alloc_cell(64);
+    assert_eq!(y[0], 0);
+    assert_eq!(y[63], 0);
+    let z = ags.alloc_cell(100);
+    assert_eq!(z[0], 7);
+    assert_eq!(z[99], 7);
+  }
+  {
+    let mut ags = StackAllocatedFreelist4::<u8>::new_allocator(&mut stack_global_buffer, bzero);
+    ags.zero_policy = ZeroPolicy::ZeroOnAlloc;
+    let mut x = ags.alloc_cell(64);
+    assert_eq!(x[0], 0);
+    x[0] = 4;
+    ags.zero_policy = ZeroPolicy::ZeroOnFree;
+    let x_mem = x.mem.as_ptr();
+    ags.free_cell(x);
+    assert_eq!(unsafe { *x_mem }, 0);
+    ags.zero_policy = ZeroPolicy::Never;
+    let mut y = ags.alloc_cell(64);
+    y[1] = 5;
+    ags.free_cell(y);
+    let z = ags.alloc_cell(64);
+    assert_eq!(z[1], 5);
+  }
+}
+
+#[test]
+fn secure_zero_test() {
+  let mut key = [0x5au8; 32];
+  secure_zero(&mut key);
+  assert_eq!(key, [0u8; 32]);
+  let mut stack_global_buffer = define_allocator_memory_pool!(4, u64, [0; 1024], stack);
+  {
+    // wipe on free for a bare StackAllocator
+    let mut ags = StackAllocatedFreelist4::<u64>::new_allocator(&mut stack_global_buffer, secure_zero);
+    ags.zero_policy = ZeroPolicy::ZeroOnFree;
+    let mut x = ags.alloc_cell(16);
+    x[15] = 0x5a5a;
+    ags.free_cell(x);
+  }
+  assert_eq!(stack_global_buffer[15], 0);
+  {
+    // or around any allocator
+    let mut ags = Zeroizing::new(StackAllocatedFreelist4::<u64>::new_allocator(&mut stack_global_buffer, uninitialized));
+    ags.inner.zero_policy = ZeroPolicy::Never;
+    let mut x = ags.alloc_cell(16);
+    x[15] = 0x5a5a;
+    ags.free_cell(x);
+  }
+  assert_eq!(stack_global_buffer[15], 0);
+}
+
+#[cfg(not(feature="no-stdlib"))]
+#[test]
+fn zeroizing_heap_test() {
+  let mut heap_global_buffer = define_allocator_memory_pool!(4, u8, [0; 1024], heap);
+  {
+    let mut ags = Zeroizing::new(StackAllocatedFreelist4::<u8>::new_allocator(&mut heap_global_buffer, uninitialized));
+    ags.inner.zero_policy = ZeroPolicy::Never;
+    let mut x = ags.alloc_cell(32);
+    x[31] = 0x5a;
+    ags.free_cell(x);
+    // a cell dropped without free_cell is wiped as well
+    let mut y = ags.alloc_cell(64);
+    y[63] = 0x5a;
+  }
+  assert!(heap_global_buffer.iter().all(|item| *item == 0));
+  let mut ags = Zeroizing::new(HeapAlloc::<u8>::new(0));
+  let mut x = ags.alloc_cell(32);
+  x[31] = 0x5a;
+  ags.free_cell(x);
+  assert!(ags.try_alloc_cell(16).is_some());
+}
+
+#[cfg(all(target_os="linux",
+          any(target_arch="x86", target_arch="x86_64", target_arch="arm", target_arch="aarch64",
+              target_arch="riscv64")))]
+#[test]
+fn mlock_pool_test() {
+  // small enough for the default RLIMIT_MEMLOCK, which some containers set to 0
+  let mut locked_global_buffer = match unsafe{define_allocator_memory_pool!(64, u8, [0; 16 * 1024], mlock)} {
+    Some(pool) => pool,
+    None => match std::io::Error::last_os_error().raw_os_error() {
+      Some(1) | Some(12) => return, // EPERM, ENOMEM
+      _ => panic!("mlock"),
+    },
+  };
+  let mut ags = LockedAllocatedFreelist64::<u8>::new_allocator(&mut locked_global_buffer.data, bzero);
+  {
+    let mut x = ags.alloc_cell(9999);
+    x.slice_mut()[0] = 4;
+    let mut y = ags.alloc_cell(4);
+    y[0] = 5;
+    ags.free_cell(y);
+    let z = ags.alloc_cell(4);
+    assert_eq!(x[0], 4);
+    assert_eq!(z[0], 0);
+    assert_eq!(x[9998], 0);
+  }
+}
+
+#[cfg(all(not(feature="no-stdlib"), target_os="linux",
+          any(target_arch="x86", target_arch="x86_64", target_arch="arm", target_arch="aarch64",
+              target_arch="riscv64")))]
+#[test]
+fn mapped_file_pool_test() {
+  use alloc_no_stdlib::MappedFilePool;
+  let path = std::env::temp_dir().join(format!("alloc_no_stdlib_pool_{}", std::process::id()));
+  {
+    let mut pool = MappedFilePool::<16>::create(&path, 65536).unwrap();
+    let mut ags = pool.allocator(bzero);
+    let mut x = ags.alloc_cell(9999);
+    x[0] = 4;
+    x[9998] = 5;
+    let mut y = ags.alloc_cell(4);
+    y[0] = 6;
+    ags.free_cell(y);
+    // other cells are found again through handles stored in the root
+    let mut names = ags.alloc_cell(100);
+    names[99] = 7;
+    let handle = ags.handle(&names);
+    x.slice_mut()[1..9].copy_from_slice(&(handle.offset as u64).to_le_bytes());
+    x.slice_mut()[9..17].copy_from_slice(&(handle.len as u64).to_le_bytes());
+    ags.set_root(&x);
+  }
+  {
+    // a later run finds the table through the root and cannot allocate over it
+    let mut pool = MappedFilePool::<16>::open(&path).unwrap();
+    let mut ags = pool.allocator(bzero);
+    let x = ags.take_root().unwrap();
+    assert!(ags.take_root().is_none());
+    assert_eq!(x.slice().len(), 9999);
+    assert_eq!(x[0], 4);
+    assert_eq!(x[9998], 5);
+    let mut word = [0u8; 8];
+    word.copy_from_slice(&x.slice()[1..9]);
+    let offset = u64::from_le_bytes(word) as usize;
+    word.copy_from_slice(&x.slice()[9..17]);
+    let names = unsafe { ags.cell_at(FreeRegion{offset : offset, len : u64::from_le_bytes(word) as usize}) };
+    assert_eq!(names.slice().len(), 100);
+    assert_eq!(names[99], 7);
+    let x_range = x.mem.as_ptr() as usize..x.mem.as_ptr() as usize + x.mem.len();
+    // the freed cell comes back zeroed, the rest of the pool was never handed out
+    let z = ags.alloc_cell(4);
+    assert_eq!(z[0], 0);
+    let w = ags.alloc_cell(40000);
+    for cell in [&z, &w].iter() {
+      assert!(!x_range.contains(&(cell.mem.as_ptr() as usize)));
+    }
+    ags.free_cell(z);
+    ags.free_cell(w);
+    core::mem::forget(ags);
+  }
+  // the second run never synced after allocating, but its free list is still consistent
+  assert_eq!(MappedFilePool::<16>::open(&path).err().unwrap().kind(), std::io::ErrorKind::InvalidData);
+  {
+    let mut pool = MappedFilePool::<16>::open_recover(&path).unwrap();
+    let mut ags = pool.allocator(bzero);
+    // freeing the root clears it, so the file still opens
+    let x = ags.take_root().unwrap();
+    ags.free_cell(x);
+  }
+  {
+    let mut pool = MappedFilePool::<16>::open(&path).unwrap();
+    let mut ags = pool.allocator(bzero);
+    assert!(ags.take_root().is_none());
+  }
+  assert!(MappedFilePool::<8>::open(&path).is_err());
+  std::fs::write(&path, b"not a pool file at all, just some bytes that are long enough").unwrap();
+  assert!(MappedFilePool::<16>::open(&path).is_err());
+  std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn offset_freelist_pool_test() {
+  {
+  let mut stack_global_buffer = define_allocator_memory_pool!(4, u8, [0; 65536], stack);
+  let mut freelist = OffsetFreelist::<4>::new(65536);
+  let mut ags = OffsetStackAllocator::new(&mut stack_global_buffer, &mut freelist, bzero);
+  {
+    let mut x = ags.alloc_cell(9999);
+    x.slice_mut()[0] = 4;
+    let mut y = ags.alloc_cell(4);
+    y[0] = 5;
+    ags.free_cell(y);
+
+    let mut three = ags.alloc_cell(3);
+    three[0] = 6;
+    ags.free_cell(three);
+
+    let mut z = ags.alloc_cell(4);
+    z.slice_mut()[1] = 8;
+    let mut reget_three = ags.alloc_cell(4);
+    reget_three.slice_mut()[1] = 9;
+    //y.mem[0] = 6; // <-- this is an error (use after free)
+    assert_eq!(x[0], 4);
+    assert_eq!(z[0], 0);
+    assert_eq!(z[1], 8);
+    assert_eq!(reget_three[0], 0);
+    assert_eq!(reget_three[1], 9);
+    let mut _z = ags.alloc_cell(1);
+  }
+  }
+}
+
+#[test]
+fn offset_freelist_relocate_test() {
+  let mut stack_global_buffer = define_allocator_memory_pool!(4, u16, [0; 4096], stack);
+  let mut freelist = OffsetFreelist::<4>::new(4096);
+  let x_region;
+  {
+    let mut ags = OffsetStackAllocator::new(&mut stack_global_buffer, &mut freelist, bzero);
+    let mut x = ags.alloc_cell(1000);
+    x[999] = 7;
+    let y = ags.alloc_cell(100);
+    ags.free_cell(y);
+    x_region = ags.region_of(&x);
+    assert_eq!(x_region, FreeRegion{offset : 0, len : 1000});
+  }
+  // both the pool and its free list are plain data: move them and carry on where we left off
+  let mut moved_buffer = stack_global_buffer;
+  let mut moved_freelist = freelist;
+  let mut ags = OffsetStackAllocator::new(&mut moved_buffer, &mut moved_freelist, bzero);
+  let x = unsafe { ags.cell_at(x_region) };
+  assert_eq!(x[999], 7);
+  let y = ags.alloc_cell(100);
+  assert_eq!(ags.region_of(&y), FreeRegion{offset : 1000, len : 100});
+  assert_eq!(y[0], 0);
+  let z = ags.alloc_cell(2996);
+  assert_eq!(ags.region_of(&z), FreeRegion{offset : 1100, len : 2996});
+  assert!(ags.try_alloc_cell(1).is_none());
+}
+
+#[test]
+#[cfg(all(not(feature="no-stdlib"), target_os="linux"))]
+fn shared_memory_pool_test() {
+  use alloc_no_stdlib::SharedMemoryPool;
+  let pool = SharedMemoryPool::<16>::create("alloc_no_stdlib_test", 65536).unwrap();
+  // a second mapping of the same object, used from another thread; this does not cover a real
+  // second process, see shared_memory_pool_fork_test for that
+  let other = SharedMemoryPool::<16>::from_file(pool.file().try_clone().unwrap()).unwrap();
+  assert_eq!(other.pool_len(), 65536);
+  let mut ags = pool.allocator(bzero);
+  let mut x = ags.alloc_cell(9999);
+  x[0] = 4;
+  x[9998] = 5;
+  let handle = ags.handle(&x);
+  let offset = handle.offset;
+  core::mem::forget(x);
+  std::thread::scope(|scope| {
+    scope.spawn(|| {
+      let mut other_ags = other.allocator(bzero);
+      let x = unsafe { other_ags.cell_at(handle) };
+      assert_eq!(x.slice().len(), 9999);
+      assert_eq!(x[0], 4);
+      assert_eq!(x[9998], 5);
+      other_ags.free_cell(x);
+      let mut y = other_ags.alloc_cell(30000);
+      y[0] = 6;
+      other_ags.free_cell(y);
+    });
+  });
+  // the free list is shared: z reuses y, freed through the other mapping, and comes back zeroed
+  let z = ags.alloc_cell(9999);
+  assert_eq!(ags.handle(&z).offset, offset + 9999);
+  assert_eq!(z[0], 0);
+  assert_eq!(z[9998], 0);
+  assert!(ags.try_alloc_cell(65536).is_none());
+  ags.free_cell(z);
+  assert!(SharedMemoryPool::<8>::from_file(pool.file().try_clone().unwrap()).is_err());
+  // a free region past the end of the pool, as a buggy peer might write it
+  use std::os::unix::fs::FileExt;
+  let last_region = SharedMemoryPool::<16>::region_len_offset(15);
+  pool.file().write_all_at(&usize::MAX.to_ne_bytes(), last_region as u64).unwrap();
+  assert_eq!(SharedMemoryPool::<16>::from_file(pool.file().try_clone().unwrap()).err().unwrap().kind(),
+             std::io::ErrorKind::InvalidData);
+  // and a mapping made before the corruption rejects it under the lock
+  let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| ags.alloc_cell(4)));
+  assert!(result.is_err());
+}
+
+#[cfg(all(not(feature="no-stdlib"), target_os="linux"))]
+extern "C" {
+  fn fork() -> i32;
+  fn waitpid(pid : i32, status : *mut i32, options : i32) -> i32;
+  fn _exit(status : i32) -> !;
+}
+
+#[test]
+#[cfg(all(not(feature="no-stdlib"), target_os="linux"))]
+fn shared_memory_pool_fork_test() {
+  use alloc_no_stdlib::SharedMemoryPool;
+  let pool = SharedMemoryPool::<16>::create("alloc_no_stdlib_fork_test", 65536).unwrap();
+  let other = SharedMemoryPool::<16>::from_file(pool.file().try_clone().unwrap()).unwrap();
+  let mut ags = pool.allocator(bzero);
+  let mut x = ags.alloc_cell(9999);
+  x[0] = 4;
+  x[9998] = 5;
+  let handle = ags.handle(&x);
+  core::mem::forget(x);
+  let pid = unsafe { fork() };
+  assert!(pid >= 0);
+  if pid == 0 {
+    // the child reports through its exit status: a panic here would unwind into the test harness
+    let mut other_ags = other.allocator(bzero);
+    let x = unsafe { other_ags.cell_at(handle) };
+    let status = if x.slice().len() == 9999 && x[0] == 4 && x[9998] == 5 { 0 } else { 1 };
+    other_ags.free_cell(x);
+    let mut y = other_ags.alloc_cell(30000);
+    y[0] = 6;
+    other_ags.free_cell(y);
+    unsafe { _exit(status) };
+  }
+  let mut status = -1;
+  assert_eq!(unsafe { waitpid(pid, &mut status, 0) }, pid);
+  assert_eq!(status, 0);
+  // the child's frees landed in the shared free list
+  let z = ags.alloc_cell(9999);
+  assert_eq!(ags.handle(&z).offset, handle.offset + 9999);
+  assert_eq!(z[0], 0);
+  assert!(ags.try_alloc_cell(65536).is_none());
+  ags.free_cell(z);
+}
+
+#[test]
+fn snapshot_restore_test() {
+  let mut stack_global_buffer = define_allocator_memory_pool!(8, u8, [0; 65536], stack);
+  let base = stack_global_buffer.as_ptr() as usize;
+  let snapshot : OffsetFreelist<8>;
+  let expected;
+  {
+    let mut ags = StackAllocatedFreelist8::<u8>::new_allocator(&mut stack_global_buffer, bzero);
+    let a = ags.alloc_cell(100);
+    let b = ags.alloc_cell(200);
+    let c = ags.alloc_cell(300);
+    let d = ags.alloc_cell(400);
+    ags.free_cell(a);
+    ags.free_cell(c);
+    snapshot = ags.snapshot();
+    assert_eq!(snapshot.free_list_start, 5);
+    assert_eq!(snapshot.dirty_watermark, 1000);
+    assert_eq!(&snapshot.regions[5..], &[FreeRegion{offset : 300, len : 300},
+                                         FreeRegion{offset : 0, len : 100},
+                                         FreeRegion{offset : 1000, len : 64536}]);
+    let mut x = ags.alloc_cell(250);
+    x[0] = 7;
+    let y = ags.alloc_cell(90);
+    expected = ((x.mem.as_ptr() as usize - base, x.slice().len()), (y.mem.as_ptr() as usize - base, y.slice().len()));
+    ags.free_cell(x);
+    ags.free_cell(y);
+    ags.free_cell(b);
+    ags.free_cell(d);
+  }
+  // a fresh allocator over the same pool replays the same fragmentation
+  let mut ags = StackAllocatedFreelist8::<u8>::new_allocator(&mut stack_global_buffer, bzero);
+  let mut w = ags.alloc_cell(2000);
+  w[1500] = 0x5a;
+  ags.free_cell(w);
+  unsafe { ags.restore(&snapshot) };
+  let mut restored = ags.snapshot::<8>();
+  assert_eq!(restored.dirty_watermark, 2000);
+  restored.dirty_watermark = snapshot.dirty_watermark;
+  assert!(restored == snapshot);
+  let x = ags.alloc_cell(250);
+  let y = ags.alloc_cell(90);
+  // memory dirtied after the snapshot is still initialized when handed out again
+  let w = ags.alloc_cell(600);
+  assert_eq!(w.mem.as_ptr() as usize - base, 1000);
+  assert_eq!(w[500], 0);
+  assert_eq!(((x.mem.as_ptr() as usize - base, x.slice().len()), (y.mem.as_ptr() as usize - base, y.slice().len())),
+             expected);
+  assert_eq!(expected, ((300, 250), (0, 100)));
+  assert_eq!(x[0], 0);
+  let mut overlapping = snapshot;
+  overlapping.regions[6] = FreeRegion{offset : 200, len : 200};
+  assert!(!overlapping.fits(65536));
+  assert!(!snapshot.fits(1000));
+  // a foreign slice on the free list has no offset in the pool and is left out
+  let mut foreign = [0u8; 32];
+  ags.free_cell(AllocatedStackMemory::<u8>{mem : &mut foreign});
+  let with_foreign = ags.snapshot::<8>();
+  assert!(with_foreign.fits(65536));
+  assert!(with_foreign.regions.iter().all(|region| region.len != 32));
+}